@@ -0,0 +1,194 @@
+//! Benchmarks of the forest operations that porc runs on every refresh, on
+//! synthetic trees of different sizes. Run with `cargo bench`.
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use porc::{Forest, Node};
+
+const SIZES: &[u32] = &[1_000, 10_000, 100_000];
+
+#[derive(Debug, Clone)]
+struct BenchNode {
+    id: u32,
+    parent: Option<u32>,
+    name: String,
+    cpu: u64,
+}
+
+impl Node for BenchNode {
+    type Id = u32;
+
+    fn id(&self) -> u32 {
+        self.id
+    }
+
+    fn parent(&self) -> Option<u32> {
+        self.parent
+    }
+
+    fn accumulate_from(&mut self, other: &Self) {
+        self.cpu += other.cpu;
+    }
+}
+
+/// A tree shaped roughly like a process tree: a few deep chains and a lot of
+/// siblings, in no particular order.
+fn nodes(size: u32) -> Vec<BenchNode> {
+    let mut nodes: Vec<BenchNode> = (0..size)
+        .map(|id| BenchNode {
+            id,
+            parent: match id {
+                0 => None,
+                id if id % 10 == 0 => Some(id - 1),
+                id => Some(id / 8),
+            },
+            name: format!("process-{}", id % 1000),
+            cpu: u64::from(id.wrapping_mul(2_654_435_761) % 100),
+        })
+        .collect();
+    nodes.reverse();
+    nodes
+}
+
+/// The next snapshot of `nodes(size)`: 1% of the nodes got a different cpu
+/// usage, and with `reparent` also a new parent.
+fn changed_nodes(size: u32, reparent: bool) -> Vec<BenchNode> {
+    let mut nodes = nodes(size);
+    for node in &mut nodes {
+        if node.id % 100 == 50 {
+            node.cpu += 1;
+            if reparent {
+                node.parent = Some(0);
+            }
+        }
+    }
+    nodes
+}
+
+fn matches(node: &BenchNode) -> bool {
+    node.name.ends_with("42")
+}
+
+fn new_forest(c: &mut Criterion) {
+    let mut group = c.benchmark_group("new_forest");
+    for &size in SIZES {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(size),
+            &nodes(size),
+            |b, nodes| {
+                b.iter_batched(
+                    || nodes.clone(),
+                    |nodes| Forest::new_forest(nodes.into_iter()),
+                    BatchSize::LargeInput,
+                )
+            },
+        );
+    }
+    group.finish();
+}
+
+fn update(c: &mut Criterion) {
+    let mut group = c.benchmark_group("update");
+    for &size in SIZES {
+        for (name, reparent) in [("changed usage", false), ("reparented", true)] {
+            let changed = changed_nodes(size, reparent);
+            group.bench_with_input(BenchmarkId::new(name, size), &changed, |b, changed| {
+                b.iter_batched(
+                    || (Forest::new_forest(nodes(size).into_iter()), changed.clone()),
+                    |(mut forest, changed)| {
+                        forest.update(changed.into_iter());
+                        forest
+                    },
+                    BatchSize::LargeInput,
+                )
+            });
+        }
+    }
+    group.finish();
+}
+
+fn filter(c: &mut Criterion) {
+    let mut group = c.benchmark_group("filter");
+    for &size in SIZES {
+        group.bench_with_input(BenchmarkId::new("in place", size), &size, |b, &size| {
+            b.iter_batched(
+                || Forest::new_forest(nodes(size).into_iter()),
+                |mut forest| {
+                    forest.filter(matches);
+                    forest
+                },
+                BatchSize::LargeInput,
+            )
+        });
+        let forest = Forest::new_forest(nodes(size).into_iter());
+        group.bench_with_input(BenchmarkId::new("filtered", size), &forest, |b, forest| {
+            b.iter(|| forest.filtered(matches))
+        });
+    }
+    group.finish();
+}
+
+fn sort_by(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sort_by");
+    for &size in SIZES {
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, &size| {
+            b.iter_batched(
+                || Forest::new_forest(nodes(size).into_iter()),
+                |mut forest| {
+                    forest.sort_by(&|a: &BenchNode, b: &BenchNode| b.cpu.cmp(&a.cpu));
+                    forest
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn render_forest_prefixes(c: &mut Criterion) {
+    let mut group = c.benchmark_group("render_forest_prefixes");
+    for &size in SIZES {
+        let forest = Forest::new_forest(nodes(size).into_iter());
+        group.bench_with_input(BenchmarkId::from_parameter(size), &forest, |b, forest| {
+            b.iter(|| forest.render_forest_prefixes().len())
+        });
+    }
+    group.finish();
+}
+
+fn into_rows(c: &mut Criterion) {
+    let mut group = c.benchmark_group("into_rows");
+    for &size in SIZES {
+        let forest = Forest::new_forest(nodes(size).into_iter());
+        group.bench_with_input(BenchmarkId::from_parameter(size), &forest, |b, forest| {
+            b.iter_batched(
+                || forest.clone(),
+                |forest| forest.into_rows(),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn row_prefixes(c: &mut Criterion) {
+    let mut group = c.benchmark_group("row_prefixes");
+    for &size in SIZES {
+        let rows = Forest::new_forest(nodes(size).into_iter()).into_rows();
+        group.bench_with_input(BenchmarkId::from_parameter(size), &rows, |b, rows| {
+            b.iter(|| rows.iter().map(|row| row.prefix(rows).len()).sum::<usize>())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    new_forest,
+    update,
+    filter,
+    sort_by,
+    render_forest_prefixes,
+    into_rows,
+    row_prefixes
+);
+criterion_main!(benches);
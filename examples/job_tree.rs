@@ -0,0 +1,70 @@
+//! Shows a tree of build jobs, keeping only the failed jobs and the jobs they
+//! belong to. Every job shows the total duration of its subtree.
+
+use porc::{Forest, Node};
+use std::fmt;
+
+struct Job {
+    id: u32,
+    parent: Option<u32>,
+    name: &'static str,
+    failed: bool,
+    seconds: u64,
+}
+
+impl Node for Job {
+    type Id = u32;
+
+    fn id(&self) -> u32 {
+        self.id
+    }
+
+    fn parent(&self) -> Option<u32> {
+        self.parent
+    }
+
+    fn accumulate_from(&mut self, other: &Self) {
+        self.seconds += other.seconds;
+    }
+}
+
+impl fmt::Display for Job {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({}s)", self.name, self.seconds)?;
+        if self.failed {
+            write!(f, " FAILED")?;
+        }
+        Ok(())
+    }
+}
+
+fn job(id: u32, parent: Option<u32>, name: &'static str, failed: bool, seconds: u64) -> Job {
+    Job {
+        id,
+        parent,
+        name,
+        failed,
+        seconds,
+    }
+}
+
+fn main() {
+    let mut forest = Forest::new_forest(
+        vec![
+            job(1, None, "release", false, 0),
+            job(2, Some(1), "build", false, 120),
+            job(3, Some(2), "compile", false, 100),
+            job(4, Some(2), "link", true, 20),
+            job(5, Some(1), "test", false, 300),
+            job(6, Some(5), "unit tests", false, 60),
+            job(7, Some(5), "integration tests", true, 240),
+            job(8, None, "nightly", false, 600),
+        ]
+        .into_iter(),
+    );
+    forest.sort_by(&|a: &Job, b: &Job| b.seconds.cmp(&a.seconds));
+    forest.filter(|job| job.failed);
+    for (prefix, job) in forest.render_forest_prefixes() {
+        println!("{prefix}{job}");
+    }
+}
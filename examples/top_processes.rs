@@ -0,0 +1,25 @@
+//! Prints the process trees of the local machine that use the most cpu,
+//! including the usage of all their descendants.
+
+use porc::grouping::GroupBy;
+use porc::{ProcessWatcher, System};
+use std::error::Error;
+use std::thread;
+use std::time::Duration;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut watcher = ProcessWatcher::new(System::new());
+    watcher.refresh()?;
+    thread::sleep(Duration::from_millis(500));
+    watcher.refresh()?;
+    let (mut forest, _anomalies) = watcher.get_forest(GroupBy::Parent);
+    forest.sort_by(&|a: &porc::Process, b: &porc::Process| b.cpu().total_cmp(&a.cpu()));
+    for tree in forest.trees().iter().take(5) {
+        let process = tree.node();
+        println!("{:>6.1}% {}", process.cpu(), process.name());
+        for child in tree.children().trees().iter().take(3) {
+            println!("{:>6.1}%   └ {}", child.node().cpu(), child.node().name());
+        }
+    }
+    Ok(())
+}
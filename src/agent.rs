@@ -0,0 +1,761 @@
+//! `porc agent` serves process snapshots over a socket or over stdin and
+//! stdout, so that the TUI can run on another machine, e.g. through an SSH
+//! tunnel or by running `ssh host porc agent --stdio`.
+//!
+//! The protocol is line based: clients send one JSON request per line and the
+//! agent answers every request with one JSON response per line.
+//!
+//! - `{"type":"snapshot"}` is answered with
+//!   `{"type":"snapshot","capacity":{"cpu":800.0,"ram":16777216000},"processes":[...]}`,
+//!   where every process looks like
+//!   `{"pid":42,"parent":1,"name":"bash","arguments":["-bash"],"cpu":0.5,"ram":4096,"cgroup":"/user.slice","user":"alice","namespace-pid":null,"stopped":false}`.
+//!   `cpu` is in percent of one core, `ram` and `capacity.ram` are in bytes.
+//!   CPU usage is measured since the previous snapshot on the same connection.
+//! - `{"type":"signal","pid":42,"signal":"SIGTERM"}` is answered with
+//!   `{"type":"ok"}`.
+//! - `{"type":"schedule","pid":42,"scheduling":{"nice":10}}` is answered with
+//!   `{"type":"ok"}`. Instead of `nice`, `scheduling` can also contain
+//!   `"io-priority":"idle"`, `"io-priority":{"best-effort":4}` (or `realtime`,
+//!   with levels from 0 to 7) or `"affinity":[0,1]`.
+//!
+//! Failed requests are answered with `{"type":"error","message":"..."}`.
+//!
+//! Signals and scheduling changes act with the privileges of the agent, so
+//! Unix sockets only accept clients running as the agent's user, and TCP
+//! connections only get snapshots unless the agent runs with
+//! `--allow-actions`.
+
+use crate::process::{Capacity, ProcessId, ProcessInfo, ProcessWatcher};
+use crate::scheduling::Scheduling;
+use crate::R;
+use nix::sys::signal::{SigSet, Signal};
+use nix::sys::socket::{getsockopt, sockopt, UnixCredentials};
+use nix::sys::stat::{umask, Mode};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::str::FromStr;
+use std::thread::JoinHandle;
+use sysinfo::Pid;
+
+/// Where an agent listens, either a path to a Unix socket or a TCP address
+/// like `localhost:7878`. Clients can also start an agent through ssh, with
+/// addresses like `ssh:build-machine`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Address {
+    Unix(PathBuf),
+    Tcp(String),
+    Ssh(String),
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Address::Unix(path) => write!(f, "{}", path.display()),
+            Address::Tcp(address) => write!(f, "{}", address),
+            Address::Ssh(host) => write!(f, "{}", host),
+        }
+    }
+}
+
+impl FromStr for Address {
+    type Err = String;
+
+    fn from_str(address: &str) -> Result<Address, String> {
+        if address.is_empty() {
+            Err("empty address".to_string())
+        } else if let Some(host) = address.strip_prefix("ssh:") {
+            Ok(Address::Ssh(host.to_string()))
+        } else if let Some(path) = address.strip_prefix("unix:") {
+            Ok(Address::Unix(PathBuf::from(path)))
+        } else if address.contains('/') {
+            Ok(Address::Unix(PathBuf::from(address)))
+        } else {
+            Ok(Address::Tcp(address.to_string()))
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+enum Request {
+    Snapshot,
+    Signal { pid: u32, signal: String },
+    Schedule { pid: u32, scheduling: Scheduling },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+enum Response {
+    Snapshot {
+        capacity: Capacity,
+        processes: Vec<ProcessInfo>,
+    },
+    Ok,
+    Error {
+        message: String,
+    },
+}
+
+/// Accepts connections until the process gets killed. Every connection gets
+/// its own `ProcessWatcher`. TCP clients don't get authenticated, so TCP
+/// addresses have to be loopback addresses unless `insecure` is set, and TCP
+/// clients can only send signals or change scheduling with `allow_actions`.
+pub(crate) fn serve(address: &Address, insecure: bool, allow_actions: bool) -> R<()> {
+    match address {
+        Address::Unix(path) => {
+            let listener = listen_unix(path)?;
+            remove_on_exit(path)?;
+            eprintln!("porc agent listening on {}", path.display());
+            accept_all(listener.incoming(), |stream| match check_peer(&stream) {
+                Ok(()) => spawn_connection(stream, true),
+                Err(error) => eprintln!("porc agent: refusing connection: {}", error),
+            });
+        }
+        Address::Tcp(address) => {
+            if !insecure && !is_loopback(address)? {
+                return Err(format!(
+                    "refusing to listen on {}, which is reachable from other machines \
+                     without authentication (use --insecure-listen to do it anyway)",
+                    address
+                )
+                .into());
+            }
+            let listener = TcpListener::bind(address)
+                .map_err(|error| format!("cannot listen on {}: {}", address, error))?;
+            eprintln!("porc agent listening on {}", listener.local_addr()?);
+            accept_all(listener.incoming(), |stream| {
+                spawn_connection(stream, allow_actions)
+            });
+        }
+        Address::Ssh(_) => return Err("porc agent cannot listen on ssh addresses".into()),
+    }
+    Ok(())
+}
+
+/// Whether all addresses the given one resolves to are loopback addresses.
+fn is_loopback(address: &str) -> R<bool> {
+    let mut addresses = address
+        .to_socket_addrs()
+        .map_err(|error| format!("invalid address {}: {}", address, error))?
+        .peekable();
+    Ok(addresses.peek().is_some() && addresses.all(|address| address.ip().is_loopback()))
+}
+
+/// Binds a socket that only the agent's user can connect to. A socket file
+/// left behind by an agent that got killed is replaced, a socket some agent
+/// still listens on isn't.
+fn listen_unix(path: &Path) -> R<UnixListener> {
+    let is_socket = std::fs::symlink_metadata(path)
+        .map(|metadata| metadata.file_type().is_socket())
+        .unwrap_or(false);
+    if is_socket {
+        if UnixStream::connect(path).is_ok() {
+            return Err(format!("another agent is listening on {}", path.display()).into());
+        }
+        std::fs::remove_file(path)
+            .map_err(|error| format!("cannot remove {}: {}", path.display(), error))?;
+    }
+    // the umask is process wide, so concurrent binds must not interleave
+    static UMASK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+    let listener = {
+        let _lock = UMASK.lock().unwrap_or_else(|error| error.into_inner());
+        let previous_umask = umask(Mode::from_bits_truncate(0o177));
+        let listener = UnixListener::bind(path);
+        umask(previous_umask);
+        listener
+    };
+    listener.map_err(|error| format!("cannot listen on {}: {}", path.display(), error).into())
+}
+
+/// Removes the socket file when the agent gets interrupted or terminated.
+/// Has to run before any other threads get started, so that they inherit
+/// the blocked signals.
+fn remove_on_exit(path: &Path) -> R<()> {
+    let mut signals = SigSet::empty();
+    signals.add(Signal::SIGINT);
+    signals.add(Signal::SIGTERM);
+    signals.thread_block()?;
+    let path = path.to_path_buf();
+    std::thread::spawn(move || {
+        let signal = signals.wait();
+        let _ = std::fs::remove_file(&path);
+        std::process::exit(128 + signal.map_or(0, |signal| signal as i32));
+    });
+    Ok(())
+}
+
+/// Only lets in clients that run as the same user as the agent.
+fn check_peer(stream: &UnixStream) -> R<()> {
+    let peer = getsockopt(stream, sockopt::PeerCredentials)?;
+    let own_uid = UnixCredentials::new().uid();
+    if peer.uid() != own_uid {
+        return Err(format!(
+            "client runs as uid {}, the agent as uid {}",
+            peer.uid(),
+            own_uid
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Failing to accept a client, e.g. because of too many open files, doesn't
+/// stop the agent.
+fn accept_all<S>(incoming: impl Iterator<Item = std::io::Result<S>>, accept: impl Fn(S)) {
+    for stream in incoming {
+        match stream {
+            Ok(stream) => accept(stream),
+            Err(error) => {
+                eprintln!("porc agent: cannot accept connection: {}", error);
+                std::thread::sleep(std::time::Duration::from_millis(100));
+            }
+        }
+    }
+}
+
+/// Serves a single client over stdin and stdout.
+pub(crate) fn serve_stdio() -> R<()> {
+    let watcher = ProcessWatcher::new(sysinfo::System::new());
+    serve_connection(
+        Pipe {
+            reader: std::io::stdin(),
+            writer: std::io::stdout(),
+        },
+        watcher,
+        true,
+    )?;
+    Ok(())
+}
+
+/// Combines a reader and a writer, e.g. stdin and stdout, into a stream.
+struct Pipe<Reader, Writer> {
+    reader: Reader,
+    writer: Writer,
+}
+
+impl<Reader: Read, Writer> Read for Pipe<Reader, Writer> {
+    fn read(&mut self, buffer: &mut [u8]) -> std::io::Result<usize> {
+        self.reader.read(buffer)
+    }
+}
+
+impl<Reader, Writer: Write> Write for Pipe<Reader, Writer> {
+    fn write(&mut self, buffer: &[u8]) -> std::io::Result<usize> {
+        self.writer.write(buffer)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+fn spawn_connection<S: Read + Write + Send + 'static>(stream: S, allow_actions: bool) {
+    std::thread::spawn(move || {
+        let watcher = ProcessWatcher::new(sysinfo::System::new());
+        if let Err(error) = serve_connection(stream, watcher, allow_actions) {
+            eprintln!("porc agent: connection failed: {}", error);
+        }
+    });
+}
+
+/// Returns the watcher when the client closes the connection. Without
+/// `allow_actions`, only snapshot requests get served.
+fn serve_connection<S: Read + Write>(
+    stream: S,
+    mut watcher: ProcessWatcher,
+    allow_actions: bool,
+) -> R<ProcessWatcher> {
+    let mut stream = BufReader::new(stream);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if stream.read_line(&mut line)? == 0 {
+            return Ok(watcher);
+        }
+        let response = match serde_json::from_str(&line) {
+            Ok(Request::Signal { .. } | Request::Schedule { .. }) if !allow_actions => {
+                Response::Error {
+                    message: "signals and scheduling changes are disabled on TCP connections, \
+                              unless the agent runs with --allow-actions"
+                        .to_string(),
+                }
+            }
+            Ok(request) => handle(&mut watcher, request),
+            Err(error) => Response::Error {
+                message: format!("invalid request: {}", error),
+            },
+        };
+        let stream = stream.get_mut();
+        serde_json::to_writer(&mut *stream, &response)?;
+        stream.write_all(b"\n")?;
+        stream.flush()?;
+    }
+}
+
+fn handle(watcher: &mut ProcessWatcher, request: Request) -> Response {
+    match request {
+        Request::Snapshot => {
+            if let Err(error) = watcher.refresh() {
+                return Response::Error {
+                    message: error.to_string(),
+                };
+            }
+            Response::Snapshot {
+                capacity: watcher.capacity(),
+                processes: watcher.processes().iter().map(ProcessInfo::from).collect(),
+            }
+        }
+        Request::Signal { pid, signal } => {
+            let result = Signal::from_str(&signal)
+                .map_err(|error| error.into())
+                .and_then(|signal| watcher.send_signal(ProcessId::Pid(Pid::from_u32(pid)), signal));
+            match result {
+                Ok(()) => Response::Ok,
+                Err(error) => Response::Error {
+                    message: error.to_string(),
+                },
+            }
+        }
+        Request::Schedule { pid, scheduling } => {
+            match watcher.schedule(ProcessId::Pid(Pid::from_u32(pid)), &scheduling) {
+                Ok(()) => Response::Ok,
+                Err(error) => Response::Error {
+                    message: error.to_string(),
+                },
+            }
+        }
+    }
+}
+
+/// The TUI's side of a connection to an agent.
+pub(crate) struct Connection {
+    stream: BufReader<Box<dyn ReadWrite>>,
+    /// `ssh` for agents started through ssh
+    child: Option<AgentCommand>,
+    /// set once the agent's command has exited, answers all further requests
+    failure: Option<String>,
+}
+
+struct AgentCommand {
+    program: String,
+    child: Child,
+    /// collects what the command writes to stderr, until it exits
+    stderr: JoinHandle<String>,
+}
+
+impl AgentCommand {
+    /// Describes why the command stopped talking to us, including what it
+    /// wrote to stderr. Gives it a second to exit before killing it.
+    fn failure(mut self) -> String {
+        let mut status = None;
+        for _ in 0..100 {
+            status = self.child.try_wait().ok().flatten();
+            if status.is_some() {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        if status.is_none() {
+            let _ = self.child.kill();
+            status = self.child.wait().ok();
+        }
+        let mut failure = match status {
+            Some(status) => format!("{} exited ({})", self.program, status),
+            None => format!("{} exited", self.program),
+        };
+        let stderr = self.stderr.join().unwrap_or_default();
+        if !stderr.trim().is_empty() {
+            failure.push_str(": ");
+            failure.push_str(stderr.trim());
+        }
+        failure
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        if let Some(AgentCommand { child, .. }) = &mut self.child {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+trait ReadWrite: Read + Write + Send {}
+
+impl<S: Read + Write + Send> ReadWrite for S {}
+
+impl std::fmt::Debug for Connection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Connection")
+    }
+}
+
+impl Connection {
+    pub(crate) fn connect(address: &Address) -> R<Connection> {
+        let stream: Box<dyn ReadWrite> = match address {
+            Address::Unix(path) => Box::new(
+                UnixStream::connect(path)
+                    .map_err(|error| format!("cannot connect to {}: {}", path.display(), error))?,
+            ),
+            Address::Tcp(address) => Box::new(
+                TcpStream::connect(address)
+                    .map_err(|error| format!("cannot connect to {}: {}", address, error))?,
+            ),
+            Address::Ssh(host) => {
+                return Connection::spawn(
+                    Command::new("ssh").args(["-T", host, "porc", "agent", "--stdio"]),
+                )
+            }
+        };
+        Ok(Connection::new(stream))
+    }
+
+    /// Talks to an agent through the stdin and stdout of a command. When the
+    /// command fails, its stderr ends up in the error.
+    fn spawn(command: &mut Command) -> R<Connection> {
+        let program = command.get_program().to_string_lossy().into_owned();
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|error| format!("cannot run {}: {}", program, error))?;
+        let pipe: Pipe<ChildStdout, ChildStdin> = Pipe {
+            reader: child.stdout.take().ok_or("agent command has no stdout")?,
+            writer: child.stdin.take().ok_or("agent command has no stdin")?,
+        };
+        let mut stderr = child.stderr.take().ok_or("agent command has no stderr")?;
+        let stderr = std::thread::spawn(move || {
+            let mut output = String::new();
+            let _ = stderr.read_to_string(&mut output);
+            output
+        });
+        let mut connection = Connection::new(Box::new(pipe));
+        connection.child = Some(AgentCommand {
+            program,
+            child,
+            stderr,
+        });
+        Ok(connection)
+    }
+
+    fn new(stream: Box<dyn ReadWrite>) -> Connection {
+        Connection {
+            stream: BufReader::new(stream),
+            child: None,
+            failure: None,
+        }
+    }
+
+    fn request(&mut self, request: &Request) -> R<Response> {
+        if let Some(failure) = &self.failure {
+            return Err(failure.clone().into());
+        }
+        let line = self.exchange(request).map_err(|error| {
+            // the agent is gone, so its command has exited or is about to
+            match self.child.take().map(AgentCommand::failure) {
+                Some(failure) => {
+                    self.failure = Some(failure.clone());
+                    failure.into()
+                }
+                None => error,
+            }
+        })?;
+        match serde_json::from_str(&line)? {
+            Response::Error { message } => Err(format!("porc agent: {}", message).into()),
+            response => Ok(response),
+        }
+    }
+
+    fn exchange(&mut self, request: &Request) -> R<String> {
+        let stream = self.stream.get_mut();
+        serde_json::to_writer(&mut *stream, request)?;
+        stream.write_all(b"\n")?;
+        stream.flush()?;
+        let mut line = String::new();
+        if self.stream.read_line(&mut line)? == 0 {
+            return Err("porc agent closed the connection".into());
+        }
+        Ok(line)
+    }
+
+    pub(crate) fn snapshot(&mut self) -> R<(Capacity, Vec<ProcessInfo>)> {
+        match self.request(&Request::Snapshot)? {
+            Response::Snapshot {
+                capacity,
+                processes,
+            } => Ok((capacity, processes)),
+            response => Err(format!("unexpected response: {:?}", response).into()),
+        }
+    }
+
+    pub(crate) fn send_signal(&mut self, pid: Pid, signal: Signal) -> R<()> {
+        self.request(&Request::Signal {
+            pid: pid.as_u32(),
+            signal: signal.as_str().to_string(),
+        })?;
+        Ok(())
+    }
+
+    pub(crate) fn schedule(&mut self, pid: Pid, scheduling: &Scheduling) -> R<()> {
+        self.request(&Request::Schedule {
+            pid: pid.as_u32(),
+            scheduling: scheduling.clone(),
+        })?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use crate::process::Process;
+    use pretty_assertions::assert_eq;
+    use std::thread::JoinHandle;
+
+    /// The agent's watcher gets returned when the connection is dropped.
+    pub(crate) fn fake_agent(processes: Vec<Process>) -> (Connection, JoinHandle<ProcessWatcher>) {
+        let (client, server) = UnixStream::pair().unwrap();
+        let agent = std::thread::spawn(move || {
+            serve_connection(server, ProcessWatcher::fake(processes), true)
+                .map_err(|error| error.to_string())
+                .unwrap()
+        });
+        (Connection::new(Box::new(client)), agent)
+    }
+
+    /// A directory for socket files that gets removed with everything in it
+    /// when dropped.
+    struct TestDirectory(PathBuf);
+
+    impl TestDirectory {
+        fn new() -> R<TestDirectory> {
+            static COUNTER: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+            let path = std::env::temp_dir().join(format!(
+                "porc-test-{}-{}",
+                std::process::id(),
+                COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+            ));
+            std::fs::create_dir(&path)?;
+            Ok(TestDirectory(path))
+        }
+    }
+
+    impl Drop for TestDirectory {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// An agent command that fails like ssh does for unknown hosts.
+    pub(crate) fn failing_agent() -> R<Connection> {
+        Connection::spawn(Command::new("sh").args([
+            "-c",
+            "echo 'ssh: Could not resolve hostname nowhere' >&2; exit 255",
+        ]))
+    }
+
+    #[test]
+    fn parses_addresses() {
+        assert_eq!(
+            "/run/porc.sock".parse(),
+            Ok(Address::Unix(PathBuf::from("/run/porc.sock")))
+        );
+        assert_eq!(
+            "unix:porc.sock".parse(),
+            Ok(Address::Unix(PathBuf::from("porc.sock")))
+        );
+        assert_eq!(
+            "localhost:7878".parse(),
+            Ok(Address::Tcp("localhost:7878".to_string()))
+        );
+        assert_eq!(
+            "ssh:build-machine".parse(),
+            Ok(Address::Ssh("build-machine".to_string()))
+        );
+    }
+
+    #[test]
+    fn recognizes_loopback_addresses() -> R<()> {
+        assert!(is_loopback("127.0.0.1:7878")?);
+        assert!(is_loopback("[::1]:7878")?);
+        assert!(is_loopback("localhost:7878")?);
+        assert!(!is_loopback("0.0.0.0:7878")?);
+        assert!(!is_loopback("192.168.1.2:7878")?);
+        assert!(is_loopback("localhost").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn refuses_to_listen_on_public_addresses() {
+        let error = serve(&Address::Tcp("0.0.0.0:0".to_string()), false, false).unwrap_err();
+        assert!(error.to_string().contains("--insecure-listen"));
+    }
+
+    #[test]
+    fn listens_on_the_same_socket_path_twice() -> R<()> {
+        let directory = TestDirectory::new()?;
+        let path = directory.0.join("porc.sock");
+        // an agent that got killed leaves its socket file behind
+        drop(listen_unix(&path)?);
+        assert!(path.exists());
+        let listener = listen_unix(&path)?;
+        let client = UnixStream::connect(&path)?;
+        let (server, _) = listener.accept()?;
+        check_peer(&server)?;
+        drop(client);
+        Ok(())
+    }
+
+    #[test]
+    fn does_not_replace_sockets_that_are_in_use() -> R<()> {
+        let directory = TestDirectory::new()?;
+        let path = directory.0.join("porc.sock");
+        let _listener = listen_unix(&path)?;
+        let error = listen_unix(&path).unwrap_err();
+        assert!(error.to_string().contains("another agent is listening"));
+        Ok(())
+    }
+
+    #[test]
+    fn does_not_replace_other_files() -> R<()> {
+        let directory = TestDirectory::new()?;
+        let path = directory.0.join("porc.sock");
+        std::fs::write(&path, "data")?;
+        assert!(listen_unix(&path).is_err());
+        assert_eq!(std::fs::read_to_string(&path)?, "data");
+        Ok(())
+    }
+
+    #[test]
+    fn only_the_agents_user_can_use_its_socket() -> R<()> {
+        use std::os::unix::fs::PermissionsExt;
+        let directory = TestDirectory::new()?;
+        let path = directory.0.join("porc.sock");
+        let _listener = listen_unix(&path)?;
+        assert_eq!(
+            std::fs::metadata(&path)?.permissions().mode() & 0o777,
+            0o600
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn keeps_accepting_after_errors() {
+        let (client, server) = UnixStream::pair().unwrap();
+        accept_all(
+            [
+                Err(std::io::Error::from_raw_os_error(libc::EMFILE)),
+                Ok(server),
+            ]
+            .into_iter(),
+            |stream| spawn_connection(stream, false),
+        );
+        let mut connection = Connection::new(Box::new(client));
+        assert!(connection.snapshot().is_ok());
+    }
+
+    #[test]
+    fn serves_snapshots() -> R<()> {
+        let (mut connection, _agent) = fake_agent(vec![
+            Process::fake(1, 2.0, None),
+            Process::fake(2, 3.0, Some(1)).with_user("alice"),
+        ]);
+        let (capacity, processes) = connection.snapshot()?;
+        assert_eq!(capacity.cpu, 400.0);
+        let processes: Vec<Process> = processes
+            .into_iter()
+            .map(|info| Process::from_info(info, 0, None))
+            .collect();
+        assert_eq!(
+            processes
+                .iter()
+                .map(|process| (process.to_string(), process.user.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("one".to_string(), None),
+                ("two".to_string(), Some("alice".to_string()))
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn forwards_signals() -> R<()> {
+        let (mut connection, agent) = fake_agent(vec![Process::fake(1, 0.0, None)]);
+        connection.send_signal(Pid::from_u32(1), Signal::SIGTERM)?;
+        drop(connection);
+        let watcher = agent.join().map_err(|_| "agent panicked")?;
+        assert_eq!(
+            watcher.sent_signals(),
+            &[(Pid::from_u32(1), Signal::SIGTERM)]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn forwards_scheduling_changes() -> R<()> {
+        let (mut connection, agent) = fake_agent(vec![Process::fake(1, 0.0, None)]);
+        connection.schedule(Pid::from_u32(1), &Scheduling::Affinity(vec![0, 2]))?;
+        drop(connection);
+        let watcher = agent.join().map_err(|_| "agent panicked")?;
+        assert_eq!(
+            watcher.schedulings(),
+            &[(Pid::from_u32(1), Scheduling::Affinity(vec![0, 2]))]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn refuses_actions_unless_allowed() -> R<()> {
+        let (client, server) = UnixStream::pair()?;
+        let agent = std::thread::spawn(move || {
+            serve_connection(server, ProcessWatcher::fake(Vec::new()), false)
+                .map_err(|error| error.to_string())
+                .unwrap()
+        });
+        let mut connection = Connection::new(Box::new(client));
+        assert!(connection.snapshot().is_ok());
+        let error = connection
+            .send_signal(Pid::from_u32(1), Signal::SIGKILL)
+            .unwrap_err();
+        assert!(error.to_string().contains("--allow-actions"));
+        assert!(connection
+            .schedule(Pid::from_u32(1), &Scheduling::Nice(19))
+            .is_err());
+        drop(connection);
+        let watcher = agent.join().map_err(|_| "agent panicked")?;
+        assert_eq!(watcher.sent_signals(), &[]);
+        assert_eq!(watcher.schedulings(), &[]);
+        Ok(())
+    }
+
+    #[test]
+    fn reports_the_stderr_of_failed_agent_commands() -> R<()> {
+        let mut connection = failing_agent()?;
+        let expected = "sh exited (exit status: 255): ssh: Could not resolve hostname nowhere";
+        assert_eq!(connection.snapshot().unwrap_err().to_string(), expected);
+        assert_eq!(connection.snapshot().unwrap_err().to_string(), expected);
+        Ok(())
+    }
+
+    #[test]
+    fn reports_invalid_requests() -> R<()> {
+        let (mut client, server) = UnixStream::pair()?;
+        std::thread::spawn(move || {
+            serve_connection(server, ProcessWatcher::fake(Vec::new()), true).is_ok()
+        });
+        client.write_all(b"{\"type\":\"reboot\"}\n")?;
+        let mut line = String::new();
+        BufReader::new(client).read_line(&mut line)?;
+        assert!(line.starts_with("{\"type\":\"error\",\"message\":\"invalid request"));
+        Ok(())
+    }
+}
@@ -0,0 +1,266 @@
+use std::fmt;
+use sysinfo::Pid;
+
+/// Reads the cgroup path of a process from `/proc/<pid>/cgroup`, preferring
+/// the unified (v2) hierarchy.
+pub(crate) fn read_cgroup(pid: Pid) -> Option<String> {
+    let contents = std::fs::read_to_string(format!("/proc/{}/cgroup", pid)).ok()?;
+    parse_cgroup_file(&contents)
+}
+
+fn parse_cgroup_file(contents: &str) -> Option<String> {
+    let entries: Vec<(&str, &str)> = contents
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(3, ':');
+            let _hierarchy = parts.next()?;
+            let controllers = parts.next()?;
+            let path = parts.next()?;
+            Some((controllers, path))
+        })
+        .collect();
+    entries
+        .iter()
+        .find(|(controllers, _)| controllers.is_empty())
+        .or_else(|| {
+            entries
+                .iter()
+                .find(|(controllers, _)| *controllers == "name=systemd")
+        })
+        .or_else(|| entries.first())
+        .map(|(_, path)| path.to_string())
+}
+
+const UNIT_SUFFIXES: &[&str] = &[".slice", ".service", ".scope", ".socket", ".mount", ".swap"];
+
+/// The systemd units a cgroup path is nested in, outermost first, e.g.
+/// `["system.slice", "nginx.service"]`.
+pub(crate) fn units(path: &str) -> Vec<&str> {
+    path.split('/')
+        .filter(|segment| !segment.is_empty())
+        .take_while(|segment| UNIT_SUFFIXES.iter().any(|suffix| segment.ends_with(suffix)))
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Runtime {
+    Docker,
+    Podman,
+    Containerd,
+    SystemdNspawn,
+}
+
+impl Runtime {
+    fn name(self) -> &'static str {
+        match self {
+            Runtime::Docker => "docker",
+            Runtime::Podman => "podman",
+            Runtime::Containerd => "containerd",
+            Runtime::SystemdNspawn => "nspawn",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct Container {
+    pub(crate) runtime: Runtime,
+    pub(crate) id: String,
+}
+
+impl fmt::Display for Container {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.runtime.name(), short_id(&self.id))
+    }
+}
+
+/// Container ids are long hex strings, like git hashes they're usually shown
+/// abbreviated.
+fn short_id(id: &str) -> &str {
+    if id.len() == 64 && id.chars().all(|c| c.is_ascii_hexdigit()) {
+        &id[..12]
+    } else {
+        id
+    }
+}
+
+impl Container {
+    pub(crate) fn from_cgroup(path: &str) -> Option<Container> {
+        let segments: Vec<&str> = path.split('/').collect();
+        for (i, segment) in segments.iter().enumerate().rev() {
+            let container = |runtime, id: &str| {
+                Some(Container {
+                    runtime,
+                    id: id.to_string(),
+                })
+            };
+            if let Some(id) = scope(segment, "docker-") {
+                return container(Runtime::Docker, id);
+            }
+            if let Some(id) = scope(segment, "libpod-") {
+                if !id.starts_with("conmon-") {
+                    return container(Runtime::Podman, id);
+                }
+            }
+            if let Some(id) = scope(segment, "cri-containerd-") {
+                return container(Runtime::Containerd, id);
+            }
+            if let Some(name) = segment
+                .strip_prefix("systemd-nspawn@")
+                .and_then(|rest| rest.strip_suffix(".service"))
+            {
+                return container(Runtime::SystemdNspawn, &unescape_unit_name(name));
+            }
+            // cgroup v1 and cgroupfs driver layouts: `/docker/<id>`,
+            // `/libpod_parent/libpod-<id>`, `/kubepods/.../<id>`
+            if i == 0 {
+                continue;
+            }
+            if let Some(id) = segment.strip_prefix("libpod-") {
+                if segments[i - 1] == "libpod_parent" && is_container_id(id) {
+                    return container(Runtime::Podman, id);
+                }
+            }
+            if is_container_id(segment) {
+                if segments[i - 1] == "docker" {
+                    return container(Runtime::Docker, segment);
+                }
+                if segments[..i].iter().any(|s| s.starts_with("kubepods")) {
+                    return container(Runtime::Containerd, segment);
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Matches systemd scope units like `docker-<id>.scope`.
+fn scope<'a>(segment: &'a str, prefix: &str) -> Option<&'a str> {
+    segment.strip_prefix(prefix)?.strip_suffix(".scope")
+}
+
+fn is_container_id(segment: &str) -> bool {
+    segment.len() == 64 && segment.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Reverses systemd's escaping of unit names, e.g. `foo\x2dbar` -> `foo-bar`.
+fn unescape_unit_name(name: &str) -> String {
+    let mut result = String::new();
+    let mut rest = name;
+    while let Some(index) = rest.find("\\x") {
+        result.push_str(&rest[..index]);
+        let escaped = rest.get(index + 2..index + 4);
+        match escaped.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+            Some(byte) => {
+                result.push(byte as char);
+                rest = &rest[index + 4..];
+            }
+            None => {
+                result.push_str("\\x");
+                rest = &rest[index + 2..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const ID: &str = "3f4e5a6b7c8d9e0f1a2b3c4d5e6f7a8b9c0d1e2f3a4b5c6d7e8f9a0b1c2d3e4f";
+
+    fn container(path: &str) -> Option<String> {
+        Container::from_cgroup(path).map(|container| container.to_string())
+    }
+
+    #[test]
+    fn prefers_the_unified_hierarchy() {
+        let contents = "12:pids:/docker/foo\n1:name=systemd:/bar\n0::/baz\n";
+        assert_eq!(parse_cgroup_file(contents), Some("/baz".to_string()));
+        let contents = "12:pids:/docker/foo\n1:name=systemd:/bar\n";
+        assert_eq!(parse_cgroup_file(contents), Some("/bar".to_string()));
+    }
+
+    #[test]
+    fn systemd_units() {
+        assert_eq!(
+            units("/system.slice/nginx.service"),
+            vec!["system.slice", "nginx.service"]
+        );
+        assert_eq!(
+            units("/user.slice/user-1000.slice/session-2.scope"),
+            vec!["user.slice", "user-1000.slice", "session-2.scope"]
+        );
+        assert_eq!(
+            units("/machine.slice/libpod-abc.scope/container"),
+            vec!["machine.slice", "libpod-abc.scope"]
+        );
+        assert_eq!(units("/"), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn host_processes() {
+        assert_eq!(
+            container("/user.slice/user-1000.slice/session-2.scope"),
+            None
+        );
+        assert_eq!(container("/system.slice/docker.service"), None);
+        assert_eq!(container("/"), None);
+    }
+
+    #[test]
+    fn docker() {
+        assert_eq!(
+            container(&format!("/system.slice/docker-{}.scope", ID)),
+            Some("docker:3f4e5a6b7c8d".to_string())
+        );
+        assert_eq!(
+            container(&format!("/docker/{}", ID)),
+            Some("docker:3f4e5a6b7c8d".to_string())
+        );
+    }
+
+    #[test]
+    fn podman() {
+        assert_eq!(
+            container(&format!(
+                "/user.slice/user-1000.slice/user@1000.service/user.slice/libpod-{}.scope/container",
+                ID
+            )),
+            Some("podman:3f4e5a6b7c8d".to_string())
+        );
+        assert_eq!(
+            container(&format!("/machine.slice/libpod-conmon-{}.scope", ID)),
+            None
+        );
+        assert_eq!(
+            container(&format!("/libpod_parent/libpod-{}", ID)),
+            Some("podman:3f4e5a6b7c8d".to_string())
+        );
+    }
+
+    #[test]
+    fn containerd() {
+        assert_eq!(
+            container(&format!(
+                "/kubepods.slice/kubepods-besteffort.slice/kubepods-besteffort-pod1234.slice/cri-containerd-{}.scope",
+                ID
+            )),
+            Some("containerd:3f4e5a6b7c8d".to_string())
+        );
+        assert_eq!(
+            container(&format!("/kubepods/besteffort/pod1234/{}", ID)),
+            Some("containerd:3f4e5a6b7c8d".to_string())
+        );
+    }
+
+    #[test]
+    fn systemd_nspawn() {
+        assert_eq!(
+            container("/machine.slice/systemd-nspawn@my\\x2dbox.service/payload/system.slice"),
+            Some("nspawn:my-box".to_string())
+        );
+    }
+}
@@ -0,0 +1,179 @@
+use crate::agent::{self, Address};
+use crate::config::{Config, Settings, State};
+use crate::grouping::GroupBy;
+use crate::key_bindings::Preset;
+use crate::launch::LaunchedCommand;
+use crate::porc_app::PorcApp;
+use crate::process::{Column, ProcessWatcher, Sorting};
+use crate::regex::FilterMode;
+use crate::theme::HeatMap;
+use crate::R;
+use clap::{Parser, Subcommand};
+use std::process::ExitCode;
+use sysinfo::System;
+
+#[derive(Parser, Debug)]
+#[command(args_conflicts_with_subcommands = true)]
+pub(crate) struct Args {
+    #[command(subcommand)]
+    pub(crate) command: Option<Command>,
+    #[arg(help = "search pattern for filtering the process tree")]
+    pub(crate) pattern: Option<String>,
+    #[arg(long, value_enum, help = "how the search pattern gets interpreted")]
+    pub(crate) filter_mode: Option<FilterMode>,
+    #[arg(
+        long,
+        help = "columns to sort by, e.g. cpu or ram:asc,name (name or any column)"
+    )]
+    pub(crate) sort: Option<Sorting>,
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        help = "columns to show, e.g. pid,cpu"
+    )]
+    pub(crate) columns: Option<Vec<Column>>,
+    #[arg(long, value_enum, help = "what to group processes by")]
+    pub(crate) group_by: Option<GroupBy>,
+    #[arg(long, help = "merge sibling processes with the same executable name")]
+    pub(crate) collapse_siblings: bool,
+    #[arg(long, value_enum, help = "color cpu and ram cells by their load")]
+    pub(crate) heat_map: Option<HeatMap>,
+    #[arg(long, help = "color process names by their load, requires --heat-map")]
+    pub(crate) tint_rows: bool,
+    #[arg(long, help = "refresh interval in seconds")]
+    pub(crate) refresh_interval: Option<f64>,
+    #[arg(
+        long,
+        help = "color theme: dark, light, high-contrast, monochrome or a theme from the config file"
+    )]
+    pub(crate) theme: Option<String>,
+    #[arg(long, help = "disable colors, same as --theme monochrome")]
+    pub(crate) no_color: bool,
+    #[arg(
+        long,
+        value_enum,
+        help = "key binding preset, overrides the preset from the config file"
+    )]
+    pub(crate) key_bindings: Option<Preset>,
+    #[arg(
+        long,
+        value_name = "ADDRESS",
+        value_delimiter = ',',
+        help = "show the processes of one or more `porc agent`s instead, e.g. localhost:7878, /run/porc.sock or ssh:build-machine"
+    )]
+    pub(crate) connect: Vec<Address>,
+    #[arg(
+        last = true,
+        value_name = "COMMAND",
+        help = "run a command and show only its process tree, exits with the command's exit code when it finishes"
+    )]
+    pub(crate) command_line: Vec<String>,
+}
+
+#[derive(Subcommand, Debug)]
+pub(crate) enum Command {
+    /// Serve process snapshots over a socket, for `porc --connect`
+    #[command(group(clap::ArgGroup::new("transport").required(true)))]
+    Agent {
+        #[arg(
+            long,
+            value_name = "ADDRESS",
+            group = "transport",
+            help = "TCP address or Unix socket path, e.g. localhost:7878"
+        )]
+        listen: Option<Address>,
+        #[arg(
+            long,
+            requires = "listen",
+            help = "allow listening on addresses other machines can reach, \
+                    the agent doesn't authenticate clients"
+        )]
+        insecure_listen: bool,
+        #[arg(
+            long,
+            requires = "listen",
+            help = "let clients on TCP addresses send signals and change scheduling, \
+                    which any local user can then do with the agent's privileges"
+        )]
+        allow_actions: bool,
+        #[arg(
+            long,
+            group = "transport",
+            help = "serve a single client over stdin and stdout"
+        )]
+        stdio: bool,
+    },
+}
+
+/// Runs the `porc` command line interface, as the `porc` binary does.
+pub fn run() -> R<ExitCode> {
+    let mut args = Args::parse();
+    if let Some(Command::Agent {
+        listen,
+        insecure_listen,
+        allow_actions,
+        ..
+    }) = args.command.take()
+    {
+        return match listen {
+            Some(address) => agent::serve(&address, insecure_listen, allow_actions),
+            None => agent::serve_stdio(),
+        }
+        .map(|()| ExitCode::SUCCESS);
+    }
+    let command_line = std::mem::take(&mut args.command_line);
+    if !command_line.is_empty() && !args.connect.is_empty() {
+        return Err("commands can only be run when watching the local machine".into());
+    }
+    let process_watcher = if args.connect.is_empty() {
+        ProcessWatcher::new(System::new())
+    } else {
+        ProcessWatcher::connect(&std::mem::take(&mut args.connect))?
+    };
+    let config = Config::load()?;
+    let persist = config.persist;
+    let state = if persist {
+        State::load()?
+    } else {
+        State::default()
+    };
+    let mut app = PorcApp::new(
+        process_watcher,
+        Settings::new(
+            args,
+            config,
+            state,
+            std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty()),
+        )?,
+    )?;
+    if !command_line.is_empty() {
+        app.launch(LaunchedCommand::spawn(&command_line)?);
+    }
+    app.run()?;
+    if persist {
+        app.state().save()?;
+    }
+    for line in app.exported() {
+        println!("{}", line);
+    }
+    match app.take_command() {
+        Some(command) => Ok(ExitCode::from(command.finish()?)),
+        None => Ok(ExitCode::SUCCESS),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn commands_come_after_a_double_dash() -> R<()> {
+        let args = Args::try_parse_from(["porc", "make", "--", "make", "-j", "agent"])?;
+        assert_eq!(args.pattern.as_deref(), Some("make"));
+        assert_eq!(args.command_line, vec!["make", "-j", "agent"]);
+        assert!(args.command.is_none());
+        Ok(())
+    }
+}
@@ -0,0 +1,583 @@
+use crate::cli::Args;
+use crate::grouping::GroupBy;
+use crate::key_bindings::{Action, Key, KeyBinding, KeyBindings, Mode, Preset};
+use crate::process::{Column, Sorting};
+use crate::regex::FilterMode;
+use crate::theme::{BuiltinTheme, CustomTheme, HeatMap, Theme};
+use crate::R;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Contents of `$XDG_CONFIG_HOME/porc/config.toml`, e.g.:
+///
+/// ```toml
+/// sort = "cpu,name"
+/// columns = ["pid", "cpu"]
+/// refresh-interval = 2.0
+/// persist = true
+/// theme = "light"
+///
+/// [key-bindings]
+/// preset = "vim"
+///
+/// [[key-bindings.bind]]
+/// key = "ctrl+x"
+/// action = "sigkill"
+/// modes = ["process-selected"]
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub(crate) struct Config {
+    pub(crate) pattern: Option<String>,
+    pub(crate) filter_mode: Option<FilterMode>,
+    pub(crate) sort: Option<Sorting>,
+    pub(crate) columns: Option<Vec<Column>>,
+    pub(crate) group_by: Option<GroupBy>,
+    /// Whether to merge sibling processes with the same executable name.
+    pub(crate) collapse_siblings: Option<bool>,
+    pub(crate) color: Option<bool>,
+    /// name of a built-in theme or of an entry in `themes`
+    pub(crate) theme: Option<String>,
+    pub(crate) themes: HashMap<String, CustomTheme>,
+    pub(crate) heat_map: Option<HeatMap>,
+    /// Whether to color process names by their heat map color as well.
+    pub(crate) tint_rows: Option<bool>,
+    /// in seconds
+    pub(crate) refresh_interval: Option<f64>,
+    /// Whether to save the last used sort column and search pattern on exit.
+    pub(crate) persist: bool,
+    pub(crate) key_bindings: KeyBindingsConfig,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct KeyBindingsConfig {
+    pub(crate) preset: Option<Preset>,
+    pub(crate) bind: Vec<KeyBindingConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct KeyBindingConfig {
+    key: Key,
+    action: Action,
+    modes: Option<Vec<Mode>>,
+}
+
+impl Config {
+    pub(crate) fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("porc").join("config.toml"))
+    }
+
+    pub(crate) fn load() -> R<Config> {
+        match Config::path() {
+            Some(path) if path.exists() => Config::load_from(&path),
+            _ => Ok(Config::default()),
+        }
+    }
+
+    fn load_from(path: &Path) -> R<Config> {
+        let contents = std::fs::read_to_string(path)?;
+        Config::parse(&contents).map_err(|error| format!("{}: {}", path.display(), error).into())
+    }
+
+    fn parse(contents: &str) -> R<Config> {
+        Ok(toml::from_str(contents)?)
+    }
+
+    fn theme_by_name(&self, name: &str) -> R<Theme> {
+        match self.themes.get(name) {
+            Some(custom_theme) => Ok(custom_theme.theme()),
+            None => match BuiltinTheme::from_str(name, false) {
+                Ok(builtin_theme) => Ok(builtin_theme.theme()),
+                Err(_) => Err(format!("unknown theme: {}", name).into()),
+            },
+        }
+    }
+
+    /// Command line arguments win over the config file, which wins over
+    /// the `NO_COLOR` environment variable.
+    fn theme(&self, args: &Args, no_color_env: bool) -> R<Theme> {
+        let monochrome = BuiltinTheme::Monochrome.theme();
+        if args.no_color {
+            return Ok(monochrome);
+        }
+        if let Some(name) = &args.theme {
+            return self.theme_by_name(name);
+        }
+        match (self.color, &self.theme) {
+            (Some(false), _) => Ok(monochrome),
+            (_, Some(name)) => self.theme_by_name(name),
+            (None, None) if no_color_env => Ok(monochrome),
+            _ => Ok(Theme::default()),
+        }
+    }
+}
+
+/// Preferences that get saved on exit when `persist` is enabled in the config
+/// file. They take precedence over the config file, but not over command line
+/// arguments.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub(crate) struct State {
+    pub(crate) sort: Option<Sorting>,
+    pub(crate) pattern: Option<String>,
+}
+
+impl State {
+    fn path() -> Option<PathBuf> {
+        dirs::state_dir()
+            .or_else(dirs::data_local_dir)
+            .map(|dir| dir.join("porc").join("state.toml"))
+    }
+
+    pub(crate) fn load() -> R<State> {
+        match State::path() {
+            Some(path) if path.exists() => {
+                let contents = std::fs::read_to_string(&path)?;
+                toml::from_str(&contents)
+                    .map_err(|error| format!("{}: {}", path.display(), error).into())
+            }
+            _ => Ok(State::default()),
+        }
+    }
+
+    pub(crate) fn save(&self) -> R<()> {
+        if let Some(path) = State::path() {
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            std::fs::write(path, toml::to_string(self)?)?;
+        }
+        Ok(())
+    }
+}
+
+/// The result of merging command line arguments, the persisted state, the
+/// config file and the defaults, in that order of precedence.
+#[derive(Debug)]
+pub(crate) struct Settings {
+    pub(crate) pattern: String,
+    pub(crate) filter_mode: FilterMode,
+    pub(crate) sort: Sorting,
+    pub(crate) columns: Vec<Column>,
+    pub(crate) group_by: GroupBy,
+    pub(crate) collapse_siblings: bool,
+    pub(crate) theme: Theme,
+    pub(crate) heat_map: HeatMap,
+    pub(crate) tint_rows: bool,
+    pub(crate) refresh_interval: Duration,
+    pub(crate) key_bindings: KeyBindings,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            pattern: String::new(),
+            filter_mode: FilterMode::default(),
+            sort: Sorting::default(),
+            columns: Column::default_columns(),
+            group_by: GroupBy::default(),
+            collapse_siblings: false,
+            theme: Theme::default(),
+            heat_map: HeatMap::default(),
+            tint_rows: false,
+            refresh_interval: Duration::from_secs(1),
+            key_bindings: KeyBindings::default(),
+        }
+    }
+}
+
+impl Settings {
+    pub(crate) fn new(args: Args, config: Config, state: State, no_color_env: bool) -> R<Settings> {
+        let default = Settings::default();
+        Ok(Settings {
+            theme: config.theme(&args, no_color_env)?,
+            pattern: args
+                .pattern
+                .or(state.pattern)
+                .or(config.pattern)
+                .unwrap_or(default.pattern),
+            filter_mode: args
+                .filter_mode
+                .or(config.filter_mode)
+                .unwrap_or(default.filter_mode),
+            sort: args
+                .sort
+                .or(state.sort)
+                .or(config.sort)
+                .unwrap_or(default.sort),
+            columns: args.columns.or(config.columns).unwrap_or(default.columns),
+            group_by: args
+                .group_by
+                .or(config.group_by)
+                .unwrap_or(default.group_by),
+            collapse_siblings: args.collapse_siblings
+                || config
+                    .collapse_siblings
+                    .unwrap_or(default.collapse_siblings),
+            heat_map: args
+                .heat_map
+                .or(config.heat_map)
+                .unwrap_or(default.heat_map),
+            tint_rows: args.tint_rows || config.tint_rows.unwrap_or(default.tint_rows),
+            refresh_interval: match args.refresh_interval.or(config.refresh_interval) {
+                Some(seconds) if seconds > 0.0 => Duration::try_from_secs_f64(seconds)?,
+                Some(seconds) => {
+                    return Err(format!("invalid refresh interval: {}", seconds).into())
+                }
+                None => default.refresh_interval,
+            },
+            key_bindings: config.key_bindings.key_bindings(args.key_bindings),
+        })
+    }
+}
+
+impl KeyBindingsConfig {
+    pub(crate) fn key_bindings(&self, preset: Option<Preset>) -> KeyBindings {
+        KeyBindings::new(
+            preset.or(self.preset).unwrap_or_default(),
+            self.bind
+                .iter()
+                .map(|binding| KeyBinding::new(binding.key, binding.modes.clone(), binding.action))
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyEventState, KeyModifiers};
+    use pretty_assertions::assert_eq;
+    use unindent::Unindent;
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent {
+            code,
+            modifiers: KeyModifiers::NONE,
+            kind: KeyEventKind::Press,
+            state: KeyEventState::NONE,
+        }
+    }
+
+    #[test]
+    fn empty_config() -> R<()> {
+        let config = Config::parse("")?;
+        assert_eq!(config.key_bindings.preset, None);
+        assert_eq!(config.key_bindings.bind.len(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn key_bindings() -> R<()> {
+        let config = Config::parse(
+            &r#"
+                [key-bindings]
+                preset = "htop"
+
+                [[key-bindings.bind]]
+                key = "x"
+                action = "sigkill"
+                modes = ["process-selected"]
+
+                [[key-bindings.bind]]
+                key = "n"
+                action = "down"
+            "#
+            .unindent(),
+        )?;
+        let key_bindings = config.key_bindings.key_bindings(None);
+        assert_eq!(
+            key_bindings.lookup(Mode::ProcessSelected, &key(KeyCode::Char('x'))),
+            Some(Action::Sigkill)
+        );
+        assert_eq!(
+            key_bindings.lookup(Mode::Normal, &key(KeyCode::Char('x'))),
+            None
+        );
+        assert_eq!(
+            key_bindings.lookup(Mode::EditingPattern, &key(KeyCode::Char('n'))),
+            Some(Action::Down)
+        );
+        assert_eq!(
+            key_bindings.lookup(Mode::Normal, &key(KeyCode::F(10))),
+            Some(Action::Quit)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn command_line_preset_overrides_the_config_file() -> R<()> {
+        let config = Config::parse(
+            &r#"
+                [key-bindings]
+                preset = "htop"
+            "#
+            .unindent(),
+        )?;
+        let key_bindings = config.key_bindings.key_bindings(Some(Preset::Vim));
+        assert_eq!(
+            key_bindings.lookup(Mode::Normal, &key(KeyCode::Char('j'))),
+            Some(Action::Down)
+        );
+        assert_eq!(
+            key_bindings.lookup(Mode::Normal, &key(KeyCode::F(10))),
+            None
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_keys_are_reported() {
+        let error = Config::parse(
+            &r#"
+                [[key-bindings.bind]]
+                key = "foo"
+                action = "quit"
+            "#
+            .unindent(),
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("invalid key: \"foo\""));
+    }
+
+    mod settings {
+        use super::*;
+        use clap::Parser;
+        use pretty_assertions::assert_eq;
+        use ratatui::style::Color;
+
+        fn settings(args: &[&str], config: &str, state: State) -> R<Settings> {
+            settings_with_env(args, config, state, false)
+        }
+
+        fn settings_with_env(
+            args: &[&str],
+            config: &str,
+            state: State,
+            no_color_env: bool,
+        ) -> R<Settings> {
+            Settings::new(
+                Args::try_parse_from([&["porc"], args].concat())?,
+                Config::parse(&config.unindent())?,
+                state,
+                no_color_env,
+            )
+        }
+
+        #[test]
+        fn defaults() -> R<()> {
+            let settings = settings(&[], "", State::default())?;
+            assert_eq!(settings.pattern, "");
+            assert_eq!(settings.sort, Sorting::new(Column::Pid));
+            assert_eq!(settings.columns, Column::default_columns());
+            assert_eq!(settings.theme, BuiltinTheme::Dark.theme());
+            assert_eq!(settings.refresh_interval, Duration::from_secs(1));
+            Ok(())
+        }
+
+        #[test]
+        fn config_file_sets_preferences() -> R<()> {
+            let settings = settings(
+                &[],
+                r#"
+                    pattern = "foo"
+                    filter-mode = "literal"
+                    sort = "cpu"
+                    columns = ["cpu", "pid"]
+                    color = false
+                    refresh-interval = 0.5
+                    heat-map = "view"
+                    group-by = "container"
+                    tint-rows = true
+                    collapse-siblings = true
+                "#,
+                State::default(),
+            )?;
+            assert_eq!(settings.pattern, "foo");
+            assert_eq!(settings.filter_mode, FilterMode::Literal);
+            assert_eq!(settings.sort, Sorting::new(Column::Cpu));
+            assert_eq!(settings.columns, vec![Column::Cpu, Column::Pid]);
+            assert_eq!(settings.theme, BuiltinTheme::Monochrome.theme());
+            assert_eq!(settings.refresh_interval, Duration::from_millis(500));
+            assert_eq!(settings.heat_map, HeatMap::View);
+            assert_eq!(settings.group_by, GroupBy::Container);
+            assert!(settings.tint_rows);
+            assert!(settings.collapse_siblings);
+            Ok(())
+        }
+
+        #[test]
+        fn command_line_arguments_override_the_config_file() -> R<()> {
+            let settings = settings(
+                &[
+                    "bar",
+                    "--sort",
+                    "ram",
+                    "--columns",
+                    "pid,ram",
+                    "--refresh-interval",
+                    "3",
+                    "--filter-mode",
+                    "regex",
+                ],
+                r#"
+                    pattern = "foo"
+                    filter-mode = "literal"
+                    sort = "cpu"
+                    columns = ["cpu", "pid"]
+                    refresh-interval = 0.5
+                "#,
+                State::default(),
+            )?;
+            assert_eq!(settings.pattern, "bar");
+            assert_eq!(settings.filter_mode, FilterMode::Regex);
+            assert_eq!(settings.sort, Sorting::new(Column::Ram));
+            assert_eq!(settings.columns, vec![Column::Pid, Column::Ram]);
+            assert_eq!(settings.refresh_interval, Duration::from_secs(3));
+            Ok(())
+        }
+
+        #[test]
+        fn persisted_state_overrides_the_config_file_but_not_arguments() -> R<()> {
+            let state = || State {
+                sort: Some(Sorting::new(Column::Ram)),
+                pattern: Some("persisted".to_string()),
+            };
+            let config = r#"
+                pattern = "foo"
+                sort = "cpu"
+            "#;
+            let settings_without_args = settings(&[], config, state())?;
+            assert_eq!(settings_without_args.pattern, "persisted");
+            assert_eq!(settings_without_args.sort, Sorting::new(Column::Ram));
+            let settings_with_args = settings(&["bar", "--sort", "pid"], config, state())?;
+            assert_eq!(settings_with_args.pattern, "bar");
+            assert_eq!(settings_with_args.sort, Sorting::new(Column::Pid));
+            Ok(())
+        }
+
+        #[test]
+        fn no_color_flag() -> R<()> {
+            assert_eq!(
+                settings(&["--no-color"], "theme = \"light\"", State::default())?.theme,
+                BuiltinTheme::Monochrome.theme()
+            );
+            Ok(())
+        }
+
+        #[test]
+        fn built_in_themes() -> R<()> {
+            assert_eq!(
+                settings(&[], "theme = \"high-contrast\"", State::default())?.theme,
+                BuiltinTheme::HighContrast.theme()
+            );
+            assert_eq!(
+                settings(&["--theme", "light"], "theme = \"dark\"", State::default())?.theme,
+                BuiltinTheme::Light.theme()
+            );
+            Ok(())
+        }
+
+        #[test]
+        fn custom_themes() -> R<()> {
+            let settings = settings(
+                &[],
+                r##"
+                    theme = "mine"
+
+                    [themes.mine]
+                    base = "light"
+                    tree = "#268bd2"
+                    lines = "dark-gray"
+                "##,
+                State::default(),
+            )?;
+            assert_eq!(
+                settings.theme,
+                Theme {
+                    tree: Color::Rgb(0x26, 0x8b, 0xd2),
+                    lines: Color::DarkGray,
+                    ..BuiltinTheme::Light.theme()
+                }
+            );
+            Ok(())
+        }
+
+        #[test]
+        fn unknown_themes_are_reported() {
+            let error = settings(&["--theme", "foo"], "", State::default()).unwrap_err();
+            assert_eq!(error.to_string(), "unknown theme: foo");
+        }
+
+        #[test]
+        fn no_color_environment_variable() -> R<()> {
+            assert_eq!(
+                settings_with_env(&[], "", State::default(), true)?.theme,
+                BuiltinTheme::Monochrome.theme()
+            );
+            assert_eq!(
+                settings_with_env(&[], "theme = \"light\"", State::default(), true)?.theme,
+                BuiltinTheme::Light.theme()
+            );
+            assert_eq!(
+                settings_with_env(&["--theme", "dark"], "", State::default(), true)?.theme,
+                BuiltinTheme::Dark.theme()
+            );
+            Ok(())
+        }
+
+        #[test]
+        fn secondary_sort_keys() -> R<()> {
+            let settings = settings(&["--sort", "ram:asc,name"], "", State::default())?;
+            assert_eq!(settings.sort, "ram:asc,name".parse()?);
+            assert_eq!(settings.sort.to_string(), "ram:asc,name");
+            Ok(())
+        }
+
+        #[test]
+        fn invalid_sort_orders_are_reported() {
+            for sort in ["foo", "cpu:up", "cpu,"] {
+                assert!(settings(&["--sort", sort], "", State::default()).is_err());
+            }
+        }
+
+        #[test]
+        fn sort_keys_round_trip() -> R<()> {
+            let sorting: Sorting = "ns-pid,user:desc".parse()?;
+            assert_eq!(sorting.to_string(), "ns-pid,user:desc");
+            Ok(())
+        }
+
+        #[test]
+        fn the_name_column_cannot_be_shown_in_the_table() {
+            assert!(settings(&["--columns", "pid,name"], "", State::default()).is_err());
+            assert!(settings(&[], "columns = [\"name\"]", State::default()).is_err());
+        }
+
+        #[test]
+        fn invalid_refresh_intervals_are_reported() {
+            assert!(settings(&["--refresh-interval", "0"], "", State::default()).is_err());
+            assert!(settings(&["--refresh-interval", "-1"], "", State::default()).is_err());
+        }
+    }
+
+    #[test]
+    fn state_can_be_serialized_and_deserialized() -> R<()> {
+        let state = State {
+            sort: Some("cpu,name:desc".parse()?),
+            pattern: Some("foo".to_string()),
+        };
+        assert_eq!(toml::from_str::<State>(&toml::to_string(&state)?)?, state);
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_fields_are_reported() {
+        assert!(Config::parse("colour = \"red\"").is_err());
+    }
+}
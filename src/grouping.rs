@@ -0,0 +1,255 @@
+use crate::cgroup::units;
+use crate::process::{Process, ProcessId};
+use crate::tree::Node;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum GroupBy {
+    /// the plain process tree
+    #[default]
+    Parent,
+    Container,
+    /// systemd slices and services
+    Unit,
+    User,
+}
+
+const ALL_GROUP_BYS: &[GroupBy] = &[
+    GroupBy::Parent,
+    GroupBy::Container,
+    GroupBy::Unit,
+    GroupBy::User,
+];
+
+impl GroupBy {
+    pub(crate) fn next(self) -> GroupBy {
+        let index = ALL_GROUP_BYS
+            .iter()
+            .position(|group_by| *group_by == self)
+            .unwrap_or(0);
+        ALL_GROUP_BYS[(index + 1) % ALL_GROUP_BYS.len()]
+    }
+
+    pub(crate) fn description(self) -> &'static str {
+        match self {
+            GroupBy::Parent => "parent process",
+            GroupBy::Container => "container",
+            GroupBy::Unit => "systemd unit",
+            GroupBy::User => "user",
+        }
+    }
+
+    /// The (possibly nested) groups a process belongs to, outermost first.
+    /// When watching several hosts, the host is always the outermost group.
+    /// Empty for processes that don't belong to any group.
+    fn path(self, process: &Process) -> Vec<String> {
+        let mut path: Vec<String> = process.host.iter().cloned().collect();
+        path.extend(self.groups(process));
+        path
+    }
+
+    fn groups(self, process: &Process) -> Vec<String> {
+        match self {
+            GroupBy::Parent => Vec::new(),
+            GroupBy::Container => process
+                .container
+                .iter()
+                .map(|container| container.to_string())
+                .collect(),
+            GroupBy::Unit => process
+                .cgroup
+                .as_deref()
+                .map(units)
+                .unwrap_or_default()
+                .into_iter()
+                .map(str::to_string)
+                .collect(),
+            GroupBy::User => process.user.iter().cloned().collect(),
+        }
+    }
+
+    /// Adds a node for every group, e.g. for every container, and moves the
+    /// top-most processes of each group under it. Processes that don't
+    /// belong to any group stay where they are.
+    pub(crate) fn group(self, mut processes: Vec<Process>) -> Vec<Process> {
+        let paths: HashMap<ProcessId, Vec<String>> = processes
+            .iter()
+            .map(|process| (process.id(), self.path(process)))
+            .filter(|(_, path)| !path.is_empty())
+            .collect();
+        let mut groups: BTreeMap<Vec<String>, Process> = BTreeMap::new();
+        for process in processes.iter_mut() {
+            let Some(path) = paths.get(&process.id()) else {
+                continue;
+            };
+            let parent_path = process.parent().and_then(|parent| paths.get(&parent));
+            if parent_path == Some(path) {
+                continue;
+            }
+            let host_depth = usize::from(process.host.is_some());
+            for depth in 1..=path.len() {
+                let prefix = &path[..depth];
+                if !groups.contains_key(prefix) {
+                    let parent = (depth > 1).then(|| Process::group_id(&path[..depth - 1]));
+                    let mut group = Process::group(prefix, parent);
+                    group.host = process.host.clone();
+                    if depth > host_depth {
+                        match self {
+                            GroupBy::Parent => {}
+                            GroupBy::Container => group.container = process.container.clone(),
+                            GroupBy::Unit => {
+                                group.cgroup = Some(format!("/{}", prefix[host_depth..].join("/")))
+                            }
+                            GroupBy::User => group.user = process.user.clone(),
+                        }
+                    }
+                    groups.insert(prefix.to_vec(), group);
+                }
+            }
+            process.set_parent(Some(Process::group_id(path)));
+        }
+        processes.extend(groups.into_values());
+        processes
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn parents(processes: &[Process]) -> Vec<(String, Option<String>)> {
+        let names: HashMap<ProcessId, String> = processes
+            .iter()
+            .map(|process| (process.id(), process.name.clone()))
+            .collect();
+        processes
+            .iter()
+            .map(|process| {
+                (
+                    process.name.clone(),
+                    process.parent().map(|parent| names[&parent].clone()),
+                )
+            })
+            .collect()
+    }
+
+    fn entry(name: &str, parent: Option<&str>) -> (String, Option<String>) {
+        (name.to_string(), parent.map(str::to_string))
+    }
+
+    #[test]
+    fn grouping_by_parent_keeps_the_tree() {
+        let processes = vec![Process::fake(1, 0.0, None), Process::fake(2, 0.0, Some(1))];
+        assert_eq!(
+            parents(&GroupBy::Parent.group(processes)),
+            vec![entry("one", None), entry("two", Some("one"))]
+        );
+    }
+
+    #[test]
+    fn top_most_container_processes_get_moved_under_a_group_node() {
+        let processes = vec![
+            Process::fake(1, 0.0, None),
+            Process::fake(2, 0.0, Some(1)),
+            Process::fake(3, 0.0, Some(2)).with_container("a"),
+            Process::fake(4, 0.0, Some(3)).with_container("a"),
+            Process::fake(5, 0.0, Some(1)).with_container("b"),
+        ];
+        assert_eq!(
+            parents(&GroupBy::Container.group(processes)),
+            vec![
+                entry("one", None),
+                entry("two", Some("one")),
+                entry("three", Some("docker:a")),
+                entry("four", Some("three")),
+                entry("five", Some("docker:b")),
+                entry("docker:a", None),
+                entry("docker:b", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn systemd_units_get_nested() {
+        let processes = vec![
+            Process::fake(1, 0.0, None).with_cgroup("/init.scope"),
+            Process::fake(2, 0.0, Some(1)).with_cgroup("/system.slice/nginx.service"),
+            Process::fake(3, 0.0, Some(2)).with_cgroup("/system.slice/nginx.service"),
+            Process::fake(4, 0.0, Some(1)).with_cgroup("/system.slice/cron.service"),
+            Process::fake(5, 0.0, None).with_cgroup("/"),
+        ];
+        assert_eq!(
+            parents(&GroupBy::Unit.group(processes)),
+            vec![
+                entry("one", Some("init.scope")),
+                entry("two", Some("nginx.service")),
+                entry("three", Some("two")),
+                entry("four", Some("cron.service")),
+                entry("five", None),
+                entry("init.scope", None),
+                entry("system.slice", None),
+                entry("cron.service", Some("system.slice")),
+                entry("nginx.service", Some("system.slice")),
+            ]
+        );
+    }
+
+    #[test]
+    fn processes_get_moved_under_their_user_when_the_parent_belongs_to_another_user() {
+        let processes = vec![
+            Process::fake(1, 0.0, None).with_user("root"),
+            Process::fake(2, 0.0, Some(1)).with_user("alice"),
+            Process::fake(3, 0.0, Some(2)).with_user("alice"),
+            Process::fake(4, 0.0, Some(3)).with_user("root"),
+            Process::fake(5, 0.0, Some(1)).with_user("bob"),
+        ];
+        assert_eq!(
+            parents(&GroupBy::User.group(processes)),
+            vec![
+                entry("one", Some("root")),
+                entry("two", Some("alice")),
+                entry("three", Some("two")),
+                entry("four", Some("root")),
+                entry("five", Some("bob")),
+                entry("alice", None),
+                entry("bob", None),
+                entry("root", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn hosts_are_the_outermost_groups() {
+        let processes = vec![
+            Process::fake(1, 0.0, None).with_host(0, "a"),
+            Process::fake(2, 0.0, Some(1))
+                .with_host(0, "a")
+                .with_user("alice"),
+            Process::fake(1, 0.0, None).with_host(1, "b"),
+        ];
+        assert_eq!(
+            parents(&GroupBy::Parent.group(processes.clone())),
+            vec![
+                entry("one", Some("a")),
+                entry("two", Some("one")),
+                entry("one", Some("b")),
+                entry("a", None),
+                entry("b", None),
+            ]
+        );
+        assert_eq!(
+            parents(&GroupBy::User.group(processes)),
+            vec![
+                entry("one", Some("a")),
+                entry("two", Some("alice")),
+                entry("one", Some("b")),
+                entry("a", None),
+                entry("alice", Some("a")),
+                entry("b", None),
+            ]
+        );
+    }
+}
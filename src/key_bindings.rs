@@ -0,0 +1,620 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum Mode {
+    Normal,
+    EditingPattern,
+    ProcessSelected,
+    Prompting,
+}
+
+impl Mode {
+    pub(crate) const ALL: &'static [Mode] = &[
+        Mode::Normal,
+        Mode::EditingPattern,
+        Mode::ProcessSelected,
+        Mode::Prompting,
+    ];
+
+    pub(crate) fn all() -> impl Iterator<Item = Mode> {
+        Mode::ALL.iter().copied()
+    }
+
+    pub(crate) fn description(self) -> &'static str {
+        match self {
+            Mode::Normal => "normal mode",
+            Mode::EditingPattern => "editing the search pattern (other keys get typed)",
+            Mode::ProcessSelected => "processes marked",
+            Mode::Prompting => "entering a value (other keys get typed)",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum Action {
+    Quit,
+    ToggleHelp,
+    ToggleDebugOverlay,
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    HalfPageUp,
+    HalfPageDown,
+    Top,
+    Bottom,
+    SelectProcess,
+    ToggleMarked,
+    MarkMatching,
+    Unselect,
+    StartEditingPattern,
+    StopEditingPattern,
+    DeletePatternCharacter,
+    NextSortColumn,
+    ReverseSortOrder,
+    NextGrouping,
+    ToggleCollapsing,
+    ToggleExpanded,
+    Sigterm,
+    Sigkill,
+    ToggleStopped,
+    Export,
+    Renice,
+    SetIoPriority,
+    SetAffinity,
+    ApplyPrompt,
+    CancelPrompt,
+    DeletePromptCharacter,
+}
+
+impl Action {
+    pub(crate) fn description(self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::ToggleHelp => "help",
+            Action::ToggleDebugOverlay => "show porc's own timings",
+            Action::Up => "move cursor up",
+            Action::Down => "move cursor down",
+            Action::PageUp => "move cursor up a page",
+            Action::PageDown => "move cursor down a page",
+            Action::HalfPageUp => "move cursor up half a page",
+            Action::HalfPageDown => "move cursor down half a page",
+            Action::Top => "move cursor to the top",
+            Action::Bottom => "move cursor to the bottom",
+            Action::SelectProcess => "select process",
+            Action::ToggleMarked => "mark or unmark process",
+            Action::MarkMatching => "mark all processes matching the pattern",
+            Action::Unselect => "unselect all",
+            Action::StartEditingPattern => "filter processes",
+            Action::StopEditingPattern => "exit search mode",
+            Action::DeletePatternCharacter => "delete last pattern character",
+            Action::NextSortColumn => "change sort column",
+            Action::ReverseSortOrder => "reverse sort order",
+            Action::NextGrouping => "change grouping",
+            Action::ToggleCollapsing => "collapse identical siblings",
+            Action::ToggleExpanded => "expand or collapse siblings",
+            Action::Sigterm => "SIGTERM marked",
+            Action::Sigkill => "SIGKILL marked",
+            Action::ToggleStopped => "stop or continue marked",
+            Action::Export => "print marked processes and quit",
+            Action::Renice => "renice marked",
+            Action::SetIoPriority => "set I/O priority of marked",
+            Action::SetAffinity => "pin marked to cpus",
+            Action::ApplyPrompt => "apply",
+            Action::CancelPrompt => "cancel",
+            Action::DeletePromptCharacter => "delete last character",
+        }
+    }
+
+    /// Hidden actions don't show up in the help, but can still be bound.
+    fn hidden(self) -> bool {
+        self == Action::ToggleDebugOverlay
+    }
+
+    fn shown_in_status_bar(self) -> bool {
+        match self {
+            Action::Quit
+            | Action::ToggleHelp
+            | Action::SelectProcess
+            | Action::Unselect
+            | Action::StartEditingPattern
+            | Action::StopEditingPattern
+            | Action::Sigterm
+            | Action::Sigkill
+            | Action::ApplyPrompt
+            | Action::CancelPrompt => true,
+            Action::ToggleDebugOverlay
+            | Action::Up
+            | Action::Down
+            | Action::PageUp
+            | Action::PageDown
+            | Action::HalfPageUp
+            | Action::HalfPageDown
+            | Action::Top
+            | Action::Bottom
+            | Action::DeletePatternCharacter
+            | Action::NextSortColumn
+            | Action::ReverseSortOrder
+            | Action::NextGrouping
+            | Action::ToggleCollapsing
+            | Action::ToggleExpanded
+            | Action::ToggleMarked
+            | Action::MarkMatching
+            | Action::ToggleStopped
+            | Action::Export
+            | Action::Renice
+            | Action::SetIoPriority
+            | Action::SetAffinity
+            | Action::DeletePromptCharacter => false,
+        }
+    }
+}
+
+/// A key combination as written in the config file, e.g. `"ctrl+d"`, `"G"`,
+/// `"F9"` or `"pgdn"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub(crate) struct Key {
+    modifiers: KeyModifiers,
+    code: KeyCode,
+}
+
+impl TryFrom<String> for Key {
+    type Error = String;
+
+    fn try_from(key: String) -> Result<Key, String> {
+        Key::parse(&key)
+    }
+}
+
+impl Key {
+    pub(crate) fn parse(key: &str) -> Result<Key, String> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = key;
+        loop {
+            let lowercase = rest.to_lowercase();
+            if lowercase.starts_with("ctrl+") && rest.len() > "ctrl+".len() {
+                modifiers |= KeyModifiers::CONTROL;
+                rest = &rest["ctrl+".len()..];
+            } else if lowercase.starts_with("alt+") && rest.len() > "alt+".len() {
+                modifiers |= KeyModifiers::ALT;
+                rest = &rest["alt+".len()..];
+            } else {
+                break;
+            }
+        }
+        let code = match rest.to_lowercase().as_str() {
+            "enter" => KeyCode::Enter,
+            "esc" => KeyCode::Esc,
+            "tab" => KeyCode::Tab,
+            "backtab" => KeyCode::BackTab,
+            "backspace" => KeyCode::Backspace,
+            "delete" => KeyCode::Delete,
+            "insert" => KeyCode::Insert,
+            "space" => KeyCode::Char(' '),
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pgup" | "pageup" => KeyCode::PageUp,
+            "pgdn" | "pagedown" => KeyCode::PageDown,
+            lowercase => {
+                let mut chars = rest.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(char), None) if modifiers.contains(KeyModifiers::CONTROL) => {
+                        KeyCode::Char(char.to_ascii_lowercase())
+                    }
+                    (Some(char), None) => KeyCode::Char(char),
+                    _ => match lowercase
+                        .strip_prefix('f')
+                        .and_then(|n| n.parse::<u8>().ok())
+                    {
+                        Some(n) if (1..=12).contains(&n) => KeyCode::F(n),
+                        _ => return Err(format!("invalid key: {:?}", key)),
+                    },
+                }
+            }
+        };
+        Ok(Key { modifiers, code })
+    }
+
+    fn matches(&self, event: &KeyEvent) -> bool {
+        self.code == event.code
+            && normalize_modifiers(self.code, self.modifiers)
+                == normalize_modifiers(event.code, event.modifiers)
+    }
+
+    fn label(&self) -> String {
+        let code = match self.code {
+            KeyCode::Up => "↑".to_string(),
+            KeyCode::Down => "↓".to_string(),
+            KeyCode::PageUp => "PgUp".to_string(),
+            KeyCode::PageDown => "PgDn".to_string(),
+            KeyCode::Home => "HOME".to_string(),
+            KeyCode::End => "END".to_string(),
+            KeyCode::Enter => "ENTER".to_string(),
+            KeyCode::Esc => "ESC".to_string(),
+            KeyCode::Tab => "TAB".to_string(),
+            KeyCode::Backspace => "BACKSPACE".to_string(),
+            KeyCode::Char(' ') => "SPACE".to_string(),
+            KeyCode::Char(c) if self.modifiers.contains(KeyModifiers::CONTROL) => {
+                c.to_ascii_uppercase().to_string()
+            }
+            code => code.to_string(),
+        };
+        let mut label = String::new();
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            label.push_str("Ctrl+");
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            label.push_str("Alt+");
+        }
+        label + &code
+    }
+}
+
+/// Terminals report shifted characters inconsistently, so `SHIFT` is ignored
+/// for character keys. (The character itself already encodes the shift.)
+fn normalize_modifiers(code: KeyCode, modifiers: KeyModifiers) -> KeyModifiers {
+    match code {
+        KeyCode::Char(_) => modifiers - KeyModifiers::SHIFT,
+        _ => modifiers,
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct KeyBinding {
+    key: Key,
+    modes: Vec<Mode>,
+    action: Action,
+}
+
+impl KeyBinding {
+    pub(crate) fn new(key: Key, modes: Option<Vec<Mode>>, action: Action) -> KeyBinding {
+        KeyBinding {
+            key,
+            modes: modes.unwrap_or(Mode::ALL.to_vec()),
+            action,
+        }
+    }
+
+    fn matches(&self, mode: Mode, event: &KeyEvent) -> bool {
+        self.modes.contains(&mode) && self.key.matches(event)
+    }
+}
+
+fn bind(key: &str, modes: &[Mode], action: Action) -> KeyBinding {
+    KeyBinding::new(Key::parse(key).unwrap(), Some(modes.to_vec()), action)
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum Preset {
+    #[default]
+    Default,
+    Vim,
+    Htop,
+}
+
+impl Preset {
+    fn bindings(self) -> Vec<KeyBinding> {
+        use Mode::*;
+        match self {
+            Preset::Default => vec![
+                bind("ctrl+c", Mode::ALL, Action::Quit),
+                bind("q", &[Normal], Action::Quit),
+                bind("?", &[Normal, ProcessSelected], Action::ToggleHelp),
+                bind("F1", Mode::ALL, Action::ToggleHelp),
+                bind("F12", Mode::ALL, Action::ToggleDebugOverlay),
+                bind("up", Mode::ALL, Action::Up),
+                bind("down", Mode::ALL, Action::Down),
+                bind("pgup", Mode::ALL, Action::PageUp),
+                bind("pgdn", Mode::ALL, Action::PageDown),
+                bind("home", Mode::ALL, Action::Top),
+                bind("end", Mode::ALL, Action::Bottom),
+                bind("enter", &[EditingPattern], Action::StopEditingPattern),
+                bind("enter", &[Normal, ProcessSelected], Action::SelectProcess),
+                bind(
+                    "/",
+                    &[Normal, EditingPattern, ProcessSelected],
+                    Action::StartEditingPattern,
+                ),
+                bind(
+                    "tab",
+                    &[Normal, EditingPattern, ProcessSelected],
+                    Action::NextSortColumn,
+                ),
+                bind("r", &[Normal, ProcessSelected], Action::ReverseSortOrder),
+                bind("g", &[Normal, ProcessSelected], Action::NextGrouping),
+                bind("c", &[Normal, ProcessSelected], Action::ToggleCollapsing),
+                bind("e", &[Normal, ProcessSelected], Action::ToggleExpanded),
+                bind("space", &[Normal, ProcessSelected], Action::ToggleMarked),
+                bind("a", &[Normal, ProcessSelected], Action::MarkMatching),
+                bind("esc", &[EditingPattern], Action::StopEditingPattern),
+                bind("esc", &[ProcessSelected], Action::Unselect),
+                bind(
+                    "backspace",
+                    &[EditingPattern],
+                    Action::DeletePatternCharacter,
+                ),
+                bind("t", &[ProcessSelected], Action::Sigterm),
+                bind("k", &[ProcessSelected], Action::Sigkill),
+                bind("s", &[ProcessSelected], Action::ToggleStopped),
+                bind("x", &[ProcessSelected], Action::Export),
+                bind("n", &[ProcessSelected], Action::Renice),
+                bind("i", &[ProcessSelected], Action::SetIoPriority),
+                bind("p", &[ProcessSelected], Action::SetAffinity),
+                bind("enter", &[Prompting], Action::ApplyPrompt),
+                bind("esc", &[Prompting], Action::CancelPrompt),
+                bind("backspace", &[Prompting], Action::DeletePromptCharacter),
+            ],
+            Preset::Vim => {
+                let mut bindings = vec![
+                    bind("j", &[Normal, ProcessSelected], Action::Down),
+                    bind("k", &[Normal, ProcessSelected], Action::Up),
+                    bind("g", &[Normal, ProcessSelected], Action::Top),
+                    bind("G", &[Normal, ProcessSelected], Action::Bottom),
+                    bind("ctrl+d", Mode::ALL, Action::HalfPageDown),
+                    bind("ctrl+u", Mode::ALL, Action::HalfPageUp),
+                    bind("ctrl+f", Mode::ALL, Action::PageDown),
+                    bind("ctrl+b", Mode::ALL, Action::PageUp),
+                    bind("K", &[ProcessSelected], Action::Sigkill),
+                    bind("ctrl+g", &[Normal, ProcessSelected], Action::NextGrouping),
+                ];
+                bindings.extend(Preset::Default.bindings());
+                bindings
+            }
+            Preset::Htop => {
+                let mut bindings = vec![
+                    bind("F10", Mode::ALL, Action::Quit),
+                    bind("h", &[Normal, ProcessSelected], Action::ToggleHelp),
+                    bind(
+                        "F3",
+                        &[Normal, EditingPattern, ProcessSelected],
+                        Action::StartEditingPattern,
+                    ),
+                    bind(
+                        "F4",
+                        &[Normal, EditingPattern, ProcessSelected],
+                        Action::StartEditingPattern,
+                    ),
+                    bind(
+                        "F6",
+                        &[Normal, EditingPattern, ProcessSelected],
+                        Action::NextSortColumn,
+                    ),
+                    bind("I", &[Normal, ProcessSelected], Action::ReverseSortOrder),
+                    bind("F9", &[Normal], Action::SelectProcess),
+                    bind("F9", &[ProcessSelected], Action::Sigterm),
+                ];
+                bindings.extend(Preset::Default.bindings());
+                bindings
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct KeyBindings(Vec<KeyBinding>);
+
+impl Default for KeyBindings {
+    fn default() -> KeyBindings {
+        KeyBindings::new(Preset::Default, Vec::new())
+    }
+}
+
+impl KeyBindings {
+    /// Custom bindings take precedence over the bindings of the preset.
+    pub(crate) fn new(preset: Preset, custom: Vec<KeyBinding>) -> KeyBindings {
+        let mut bindings = custom;
+        bindings.extend(preset.bindings());
+        KeyBindings(bindings)
+    }
+
+    pub(crate) fn lookup(&self, mode: Mode, event: &KeyEvent) -> Option<Action> {
+        self.0
+            .iter()
+            .find(|binding| binding.matches(mode, event))
+            .map(|binding| binding.action)
+    }
+
+    /// All actions available in the given mode except hidden ones, in table
+    /// order, together with all the keys that trigger them. Keys that are
+    /// shadowed by an earlier binding are left out.
+    pub(crate) fn for_mode(&self, mode: Mode) -> Vec<(Action, Vec<String>)> {
+        let mut result: Vec<(Action, Vec<String>)> = Vec::new();
+        let mut seen_keys: Vec<Key> = Vec::new();
+        for binding in self
+            .0
+            .iter()
+            .filter(|binding| binding.modes.contains(&mode) && !binding.action.hidden())
+        {
+            if seen_keys.contains(&binding.key) {
+                continue;
+            }
+            seen_keys.push(binding.key);
+            match result
+                .iter_mut()
+                .find(|(action, _)| *action == binding.action)
+            {
+                Some((_, keys)) => keys.push(binding.key.label()),
+                None => result.push((binding.action, vec![binding.key.label()])),
+            }
+        }
+        result
+    }
+
+    pub(crate) fn status_bar_segments(&self, mode: Mode) -> Vec<String> {
+        self.for_mode(mode)
+            .into_iter()
+            .filter(|(action, _)| action.shown_in_status_bar())
+            .map(|(action, keys)| format!("{}: {}", keys[0], action.description()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crossterm::event::{KeyEventKind, KeyEventState};
+    use pretty_assertions::assert_eq;
+
+    fn key(modifiers: KeyModifiers, code: KeyCode) -> KeyEvent {
+        KeyEvent {
+            code,
+            modifiers,
+            kind: KeyEventKind::Press,
+            state: KeyEventState::NONE,
+        }
+    }
+
+    #[test]
+    fn lookup_respects_the_mode() {
+        let bindings = KeyBindings::default();
+        let enter = key(KeyModifiers::NONE, KeyCode::Enter);
+        assert_eq!(
+            bindings.lookup(Mode::Normal, &enter),
+            Some(Action::SelectProcess)
+        );
+        assert_eq!(
+            bindings.lookup(Mode::EditingPattern, &enter),
+            Some(Action::StopEditingPattern)
+        );
+        let q = key(KeyModifiers::NONE, KeyCode::Char('q'));
+        assert_eq!(bindings.lookup(Mode::EditingPattern, &q), None);
+    }
+
+    #[test]
+    fn lookup_ignores_shift_for_characters() {
+        let bindings = KeyBindings::default();
+        assert_eq!(
+            bindings.lookup(Mode::Normal, &key(KeyModifiers::SHIFT, KeyCode::Char('?'))),
+            Some(Action::ToggleHelp)
+        );
+        assert_eq!(
+            bindings.lookup(Mode::Normal, &key(KeyModifiers::SHIFT, KeyCode::Up)),
+            None
+        );
+    }
+
+    #[test]
+    fn for_mode_collects_all_keys_of_an_action() {
+        let bindings = KeyBindings::default();
+        let help = bindings
+            .for_mode(Mode::Normal)
+            .into_iter()
+            .find(|(action, _)| *action == Action::ToggleHelp);
+        assert_eq!(
+            help,
+            Some((Action::ToggleHelp, vec!["?".to_string(), "F1".to_string()]))
+        );
+    }
+
+    #[test]
+    fn for_mode_leaves_out_shadowed_keys() {
+        let bindings = KeyBindings::new(Preset::Vim, Vec::new());
+        let sigkill = bindings
+            .for_mode(Mode::ProcessSelected)
+            .into_iter()
+            .find(|(action, _)| *action == Action::Sigkill);
+        assert_eq!(sigkill, Some((Action::Sigkill, vec!["K".to_string()])));
+    }
+
+    #[test]
+    fn custom_bindings_override_the_preset() {
+        let bindings = KeyBindings::new(
+            Preset::Default,
+            vec![KeyBinding::new(
+                Key::parse("t").unwrap(),
+                Some(vec![Mode::ProcessSelected]),
+                Action::Sigkill,
+            )],
+        );
+        assert_eq!(
+            bindings.lookup(
+                Mode::ProcessSelected,
+                &key(KeyModifiers::NONE, KeyCode::Char('t'))
+            ),
+            Some(Action::Sigkill)
+        );
+    }
+
+    #[test]
+    fn presets_bind_their_keys() {
+        let vim = KeyBindings::new(Preset::Vim, Vec::new());
+        assert_eq!(
+            vim.lookup(Mode::Normal, &key(KeyModifiers::NONE, KeyCode::Char('j'))),
+            Some(Action::Down)
+        );
+        assert_eq!(
+            vim.lookup(
+                Mode::Normal,
+                &key(KeyModifiers::CONTROL, KeyCode::Char('d'))
+            ),
+            Some(Action::HalfPageDown)
+        );
+        assert_eq!(
+            vim.lookup(
+                Mode::EditingPattern,
+                &key(KeyModifiers::NONE, KeyCode::Char('j'))
+            ),
+            None
+        );
+        let htop = KeyBindings::new(Preset::Htop, Vec::new());
+        assert_eq!(
+            htop.lookup(
+                Mode::ProcessSelected,
+                &key(KeyModifiers::NONE, KeyCode::F(9))
+            ),
+            Some(Action::Sigterm)
+        );
+    }
+
+    mod parsing_keys {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        fn parse(key: &str) -> (KeyModifiers, KeyCode) {
+            let key = Key::parse(key).unwrap();
+            (key.modifiers, key.code)
+        }
+
+        #[test]
+        fn characters() {
+            assert_eq!(parse("j"), (KeyModifiers::NONE, KeyCode::Char('j')));
+            assert_eq!(parse("G"), (KeyModifiers::NONE, KeyCode::Char('G')));
+            assert_eq!(parse("+"), (KeyModifiers::NONE, KeyCode::Char('+')));
+        }
+
+        #[test]
+        fn modifiers() {
+            assert_eq!(parse("ctrl+d"), (KeyModifiers::CONTROL, KeyCode::Char('d')));
+            assert_eq!(parse("Ctrl+D"), (KeyModifiers::CONTROL, KeyCode::Char('d')));
+            assert_eq!(
+                parse("ctrl+alt+up"),
+                (KeyModifiers::CONTROL | KeyModifiers::ALT, KeyCode::Up)
+            );
+            assert_eq!(parse("ctrl++"), (KeyModifiers::CONTROL, KeyCode::Char('+')));
+        }
+
+        #[test]
+        fn named_keys() {
+            assert_eq!(parse("F9"), (KeyModifiers::NONE, KeyCode::F(9)));
+            assert_eq!(parse("pgdn"), (KeyModifiers::NONE, KeyCode::PageDown));
+            assert_eq!(parse("Enter"), (KeyModifiers::NONE, KeyCode::Enter));
+            assert_eq!(parse("space"), (KeyModifiers::NONE, KeyCode::Char(' ')));
+        }
+
+        #[test]
+        fn invalid_keys() {
+            assert!(Key::parse("foo").is_err());
+            assert!(Key::parse("F13").is_err());
+            assert!(Key::parse("").is_err());
+        }
+    }
+}
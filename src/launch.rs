@@ -0,0 +1,227 @@
+use crate::R;
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+use std::os::unix::process::ExitStatusExt;
+use std::process::{Child, ChildStderr, ChildStdout, ExitStatus, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use sysinfo::Pid;
+
+/// How much output of each stream is kept while the UI runs. Older output
+/// gets dropped.
+const BUFFER_LIMIT: usize = 1024 * 1024;
+
+/// A command started by `porc -- <command>`. Its output would garble the UI,
+/// so it's buffered until porc exits and passed through afterwards.
+#[derive(Debug)]
+pub(crate) struct LaunchedCommand {
+    program: String,
+    child: Child,
+    stdout: Output,
+    stderr: Output,
+    status: Option<ExitStatus>,
+}
+
+impl LaunchedCommand {
+    pub(crate) fn spawn(command_line: &[String]) -> R<LaunchedCommand> {
+        let (program, arguments) = command_line.split_first().ok_or("no command given")?;
+        let mut child = std::process::Command::new(program)
+            .args(arguments)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|error| format!("cannot run {}: {}", program, error))?;
+        let stdout: ChildStdout = child.stdout.take().ok_or("command has no stdout")?;
+        let stderr: ChildStderr = child.stderr.take().ok_or("command has no stderr")?;
+        Ok(LaunchedCommand {
+            program: program.clone(),
+            child,
+            stdout: Output::forward(stdout, std::io::stdout),
+            stderr: Output::forward(stderr, std::io::stderr),
+            status: None,
+        })
+    }
+
+    pub(crate) fn pid(&self) -> Pid {
+        Pid::from_u32(self.child.id())
+    }
+
+    pub(crate) fn has_exited(&mut self) -> R<bool> {
+        if self.status.is_none() {
+            self.status = self.child.try_wait()?;
+        }
+        Ok(self.status.is_some())
+    }
+
+    /// Writes the buffered output, passes through the rest and waits for the
+    /// command to exit. Returns the exit code porc should exit with.
+    pub(crate) fn finish(mut self) -> R<u8> {
+        self.stdout.pass_through(&mut std::io::stdout())?;
+        self.stderr.pass_through(&mut std::io::stderr())?;
+        if !self.has_exited()? {
+            eprintln!(
+                "porc: waiting for {} (pid {}) to exit, press Ctrl+C to interrupt it",
+                self.program,
+                self.child.id()
+            );
+        }
+        let status = match self.status {
+            Some(status) => status,
+            None => self.child.wait()?,
+        };
+        self.stdout.join();
+        self.stderr.join();
+        Ok(exit_code(status))
+    }
+}
+
+/// Like shells do, commands killed by a signal exit with 128 plus the signal.
+fn exit_code(status: ExitStatus) -> u8 {
+    match (status.code(), status.signal()) {
+        (Some(code), _) => code as u8,
+        (None, Some(signal)) => 128u8.wrapping_add(signal as u8),
+        (None, None) => 1,
+    }
+}
+
+#[derive(Debug)]
+struct Output {
+    /// `None` once the output gets passed through
+    buffer: Arc<Mutex<Option<Buffer>>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+/// Keeps the last `limit` bytes.
+#[derive(Debug)]
+struct Buffer {
+    bytes: VecDeque<u8>,
+    limit: usize,
+    dropped: usize,
+}
+
+impl Buffer {
+    fn new(limit: usize) -> Buffer {
+        Buffer {
+            bytes: VecDeque::new(),
+            limit,
+            dropped: 0,
+        }
+    }
+
+    fn extend(&mut self, chunk: &[u8]) {
+        self.bytes.extend(chunk);
+        let excess = self.bytes.len().saturating_sub(self.limit);
+        self.bytes.drain(..excess);
+        self.dropped += excess;
+    }
+
+    fn write_to(&self, writer: &mut impl Write) -> R<()> {
+        if self.dropped > 0 {
+            writeln!(
+                writer,
+                "[porc: dropped {} bytes of earlier output]",
+                self.dropped
+            )?;
+        }
+        let (first, second) = self.bytes.as_slices();
+        writer.write_all(first)?;
+        writer.write_all(second)?;
+        Ok(())
+    }
+}
+
+impl Output {
+    fn forward<W: Write + 'static>(
+        mut reader: impl Read + Send + 'static,
+        writer: fn() -> W,
+    ) -> Output {
+        let buffer = Arc::new(Mutex::new(Some(Buffer::new(BUFFER_LIMIT))));
+        let thread = std::thread::spawn({
+            let buffer = Arc::clone(&buffer);
+            move || {
+                let mut chunk = [0; 8192];
+                while let Ok(length @ 1..) = reader.read(&mut chunk) {
+                    let Ok(mut buffer) = buffer.lock() else {
+                        return;
+                    };
+                    match buffer.as_mut() {
+                        Some(buffer) => buffer.extend(&chunk[..length]),
+                        None => {
+                            let mut writer = writer();
+                            let _ = writer.write_all(&chunk[..length]);
+                            let _ = writer.flush();
+                        }
+                    }
+                }
+            }
+        });
+        Output {
+            buffer,
+            thread: Some(thread),
+        }
+    }
+
+    fn pass_through(&mut self, writer: &mut impl Write) -> R<()> {
+        let mut buffer = self.buffer.lock().map_err(|_| "output thread panicked")?;
+        if let Some(buffered) = buffer.take() {
+            buffered.write_to(writer)?;
+            writer.flush()?;
+        }
+        Ok(())
+    }
+
+    fn join(&mut self) {
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn run(command_line: &[&str]) -> R<u8> {
+        let command_line: Vec<String> = command_line.iter().map(|s| s.to_string()).collect();
+        LaunchedCommand::spawn(&command_line)?.finish()
+    }
+
+    #[test]
+    fn forwards_the_exit_code() -> R<()> {
+        assert_eq!(run(&["true"])?, 0);
+        assert_eq!(run(&["sh", "-c", "exit 3"])?, 3);
+        assert_eq!(run(&["sh", "-c", "kill -TERM $$"])?, 128 + 15);
+        Ok(())
+    }
+
+    #[test]
+    fn notices_when_the_command_exits() -> R<()> {
+        let mut command = LaunchedCommand::spawn(&["true".to_string()])?;
+        while !command.has_exited()? {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert_eq!(command.finish()?, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn keeps_only_the_end_of_long_output() -> R<()> {
+        let mut buffer = Buffer::new(4);
+        buffer.extend(b"abc");
+        buffer.extend(b"defg");
+        let mut output = Vec::new();
+        buffer.write_to(&mut output)?;
+        assert_eq!(
+            String::from_utf8(output)?,
+            "[porc: dropped 3 bytes of earlier output]\ndefg"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn reports_missing_commands() {
+        assert!(run(&["porc-test-no-such-command"]).is_err());
+    }
+}
@@ -0,0 +1,77 @@
+//! `porc` is a process tree viewer for the terminal. Besides the binary this
+//! crate exposes the engine behind it:
+//!
+//! - [`tree`]: a generic [`Forest`] of [`Node`]s that can be built from a flat
+//!   list of nodes, sorted, filtered (keeping ancestors and descendants of
+//!   matches) and rendered with tree prefixes.
+//! - [`process`]: [`Process`]es and the [`ProcessWatcher`] that reads them from
+//!   the system.
+//! - [`grouping`]: the ways `porc` can group processes.
+//!
+//! ```
+//! use porc::{Forest, Node};
+//!
+//! struct Job {
+//!     id: u32,
+//!     parent: Option<u32>,
+//!     name: &'static str,
+//!     failed: usize,
+//! }
+//!
+//! impl Node for Job {
+//!     type Id = u32;
+//!
+//!     fn id(&self) -> u32 {
+//!         self.id
+//!     }
+//!
+//!     fn parent(&self) -> Option<u32> {
+//!         self.parent
+//!     }
+//!
+//!     fn accumulate_from(&mut self, other: &Self) {
+//!         self.failed += other.failed;
+//!     }
+//! }
+//!
+//! let mut forest = Forest::new_forest(
+//!     vec![
+//!         Job { id: 1, parent: None, name: "deploy", failed: 0 },
+//!         Job { id: 2, parent: Some(1), name: "build", failed: 1 },
+//!         Job { id: 3, parent: Some(1), name: "test", failed: 0 },
+//!     ]
+//!     .into_iter(),
+//! );
+//! forest.filter(|job| job.name == "build");
+//! let rows: Vec<String> = forest
+//!     .render_forest_prefixes()
+//!     .into_iter()
+//!     .map(|(prefix, job)| format!("{prefix}{} ({} failed)", job.name, job.failed))
+//!     .collect();
+//! assert_eq!(rows, vec!["deploy (1 failed)", "└── build (1 failed)"]);
+//! ```
+
+mod agent;
+mod cgroup;
+mod cli;
+mod config;
+pub mod grouping;
+mod key_bindings;
+mod launch;
+mod namespace;
+mod porc_app;
+pub mod process;
+mod regex;
+mod scheduling;
+mod theme;
+pub mod tree;
+mod tui_app;
+mod utils;
+
+pub use cli::run;
+pub use nix::sys::signal::Signal;
+pub use process::{Process, ProcessId, ProcessWatcher};
+pub use sysinfo::{Pid, System};
+pub use tree::{Anomaly, Forest, Node, Tree};
+
+type R<A> = Result<A, Box<dyn std::error::Error>>;
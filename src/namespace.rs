@@ -0,0 +1,40 @@
+use sysinfo::Pid;
+
+/// Reads the pid of a process in its own pid namespace from the `NSpid` line
+/// of `/proc/<pid>/status`. Returns `None` when that line has a single entry,
+/// i.e. for processes in the pid namespace `/proc` was mounted from.
+pub(crate) fn read_namespace_pid(pid: Pid) -> Option<u32> {
+    let contents = std::fs::read_to_string(format!("/proc/{}/status", pid)).ok()?;
+    parse_status_file(&contents)
+}
+
+fn parse_status_file(contents: &str) -> Option<u32> {
+    let pids: Vec<u32> = contents
+        .lines()
+        .find_map(|line| line.strip_prefix("NSpid:"))?
+        .split_whitespace()
+        .map(str::parse)
+        .collect::<Result<_, _>>()
+        .ok()?;
+    match pids.as_slice() {
+        [_host, .., innermost] => Some(*innermost),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn processes_in_the_same_namespace() {
+        assert_eq!(parse_status_file("Name:\tbash\nNSpid:\t1234\n"), None);
+        assert_eq!(parse_status_file("Name:\tbash\n"), None);
+    }
+
+    #[test]
+    fn nested_namespaces() {
+        assert_eq!(parse_status_file("NSpid:\t1234\t1\n"), Some(1));
+        assert_eq!(parse_status_file("NSpid:\t1234\t56\t7\n"), Some(7));
+    }
+}
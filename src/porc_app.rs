@@ -1,252 +1,935 @@
+use crate::config::{Settings, State};
+use crate::grouping::GroupBy;
+use crate::key_bindings::{Action, KeyBindings, Mode};
+use crate::launch::LaunchedCommand;
 use crate::process::ProcessWatcher;
-use crate::process::SortBy;
+use crate::process::{Capacity, Column, ProcessId, Sorting};
 use crate::regex::Regex;
-use crate::tree::Forest;
+use crate::scheduling::{self, Scheduling};
+use crate::theme::{HeatMap, Theme};
+use crate::tree::{Forest, Row};
 use crate::{
     process::Process,
     tree::Node,
     tui_app::{self, UpdateResult},
     R,
 };
-use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
-use nix::sys::signal::kill;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
+use nix::sys::signal::Signal;
 use ratatui::{
     buffer::Buffer,
     layout::Rect,
-    style::Stylize,
+    style::{Color, Stylize},
     text::Line,
-    widgets::{List, ListState, Paragraph, StatefulWidget, Widget},
+    widgets::{Block, Clear, List, ListState, Paragraph, Widget, Wrap},
 };
+use std::collections::HashSet;
+use std::ops::Range;
+use std::time::{Duration, Instant};
+
+const DOUBLE_CLICK_INTERVAL: Duration = Duration::from_millis(400);
+const MOUSE_SCROLL_LINES: usize = 3;
+const MIN_WIDTH: u16 = 40;
+const MIN_HEIGHT: u16 = 4;
+const MAX_STATUS_BAR_HEIGHT: usize = 2;
+const PAGE_SIZE: usize = 20;
 
 #[derive(Debug)]
 pub(crate) struct PorcApp {
     process_watcher: ProcessWatcher,
-    forest: Forest<Process>,
+    /// the grouped processes, updated in place on every refresh
+    processes: Forest<Process>,
+    /// only the subtree of this process is shown, see `launch`
+    zoom: Option<ProcessId>,
+    command: Option<LaunchedCommand>,
+    /// `processes` filtered, collapsed and sorted, in display order
+    rows: Vec<Row<Process>>,
+    /// whether something that `rows` depend on has changed
+    rows_outdated: bool,
+    /// the maximum usage in `rows`, for the heat map
+    rows_capacity: Capacity,
     pattern: Regex,
     list_state: ListState,
     ui_mode: UiMode,
-    sort_column: SortBy,
+    /// processes that actions like sending signals apply to, `ui_mode` is
+    /// `ProcessSelected` while this isn't empty
+    marked: HashSet<ProcessId>,
+    /// lines to print after exiting, see `Action::Export`
+    exported: Vec<String>,
+    /// what has been typed in `UiMode::Prompting`
+    input: String,
+    /// shown in the status bar until the next key press, e.g. failed renices
+    message: Option<String>,
+    sorting: Sorting,
+    columns: Vec<Column>,
+    theme: Theme,
+    heat_map: HeatMap,
+    tint_rows: bool,
+    group_by: GroupBy,
+    collapse_siblings: bool,
+    /// collapsed rows that have been expanded
+    expanded: HashSet<ProcessId>,
+    refresh_interval: Duration,
+    header_rect: Rect,
+    list_rect: Rect,
+    last_click: Option<(Instant, usize)>,
+    key_bindings: KeyBindings,
+    help_visible: bool,
+    /// first visible line of the help overlay
+    help_scroll: usize,
+    /// lines that fit into the help overlay, for paging
+    help_height: usize,
+    debug_overlay_visible: bool,
+    timings: Timings,
+    /// inconsistencies in the last snapshot, e.g. processes whose parent
+    /// exited while taking it, shown in the debug overlay
+    anomalies: usize,
+}
+
+/// How long porc itself took for its last refresh and render.
+#[derive(Debug, Default)]
+struct Timings {
+    refresh: Duration,
+    build: Duration,
+    filter: Duration,
+    render: Duration,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum UiMode {
     Normal,
     EditingPattern,
-    ProcessSelected(sysinfo::Pid),
+    ProcessSelected,
+    Prompting(Prompt),
+}
+
+/// Asks for a value to apply to the marked processes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Prompt {
+    Nice,
+    IoPriority,
+    Affinity,
+}
+
+impl Prompt {
+    fn label(self) -> &'static str {
+        match self {
+            Prompt::Nice => "nice value (-20 to 19)",
+            Prompt::IoPriority => "I/O priority (idle, be:0-7 or rt:0-7)",
+            Prompt::Affinity => "cpus (e.g. 0-3,6)",
+        }
+    }
+
+    fn parse(self, input: &str) -> Result<Scheduling, String> {
+        Ok(match self {
+            Prompt::Nice => Scheduling::Nice(scheduling::parse_nice(input)?),
+            Prompt::IoPriority => Scheduling::IoPriority(input.parse()?),
+            Prompt::Affinity => Scheduling::Affinity(scheduling::parse_cpu_list(input)?),
+        })
+    }
+}
+
+impl UiMode {
+    fn mode(self) -> Mode {
+        match self {
+            UiMode::Normal => Mode::Normal,
+            UiMode::EditingPattern => Mode::EditingPattern,
+            UiMode::ProcessSelected => Mode::ProcessSelected,
+            UiMode::Prompting(_) => Mode::Prompting,
+        }
+    }
 }
 
 impl PorcApp {
-    pub(crate) fn new(process_watcher: ProcessWatcher, pattern: Option<Regex>) -> R<PorcApp> {
+    pub(crate) fn new(process_watcher: ProcessWatcher, settings: Settings) -> R<PorcApp> {
         Ok(PorcApp {
             process_watcher,
-            forest: Forest::empty(),
-            pattern: pattern.unwrap_or(Regex::empty()?),
+            processes: Forest::empty(),
+            zoom: None,
+            command: None,
+            rows: Vec::new(),
+            rows_outdated: true,
+            rows_capacity: Capacity::default(),
+            pattern: Regex::from_pattern(settings.pattern.clone(), settings.filter_mode),
             list_state: ListState::default().with_selected(Some(0)),
             ui_mode: UiMode::Normal,
-            sort_column: SortBy::default(),
+            marked: HashSet::new(),
+            exported: Vec::new(),
+            input: String::new(),
+            // e.g. a saved regex that doesn't parse in literal mode
+            message: Regex::parse(&settings.pattern, settings.filter_mode)
+                .is_err()
+                .then(|| format!("invalid search pattern: {}", settings.pattern)),
+            sorting: settings.sort,
+            columns: settings.columns,
+            theme: settings.theme,
+            heat_map: settings.heat_map,
+            tint_rows: settings.tint_rows,
+            group_by: settings.group_by,
+            collapse_siblings: settings.collapse_siblings,
+            expanded: HashSet::new(),
+            refresh_interval: settings.refresh_interval,
+            header_rect: Rect::default(),
+            list_rect: Rect::default(),
+            last_click: None,
+            key_bindings: settings.key_bindings,
+            help_visible: false,
+            help_scroll: 0,
+            help_height: 0,
+            debug_overlay_visible: false,
+            timings: Timings::default(),
+            anomalies: 0,
         })
     }
 
-    pub(crate) fn run(self) -> R<()> {
-        tui_app::run_ui(self)
+    pub(crate) fn run(&mut self) -> R<()> {
+        let refresh_interval = self.refresh_interval;
+        tui_app::run_ui(self, refresh_interval)
+    }
+
+    pub(crate) fn state(&self) -> State {
+        State {
+            sort: Some(self.sorting.clone()),
+            pattern: self
+                .pattern
+                .is_valid()
+                .then(|| self.pattern.as_str().to_string()),
+        }
+    }
+
+    /// Shows only the command's subtree and exits when the command does.
+    pub(crate) fn launch(&mut self, command: LaunchedCommand) {
+        self.zoom = Some(ProcessId::Pid(command.pid()));
+        self.command = Some(command);
+        self.rows_outdated = true;
+    }
+
+    pub(crate) fn take_command(&mut self) -> Option<LaunchedCommand> {
+        self.command.take()
+    }
+
+    pub(crate) fn exported(&self) -> &[String] {
+        &self.exported
     }
 
     fn update_processes(&mut self) {
-        self.forest = self.process_watcher.get_forest();
-        self.forest
-            .sort_by(&|a, b| Process::compare(a, b, self.sort_column));
-        self.forest.filter(|p| {
-            self.pattern.is_match(&p.name) || self.pattern.is_match(&p.id().to_string())
-        });
-        if let UiMode::ProcessSelected(selected) = self.ui_mode {
-            if !self.forest.iter().any(|node| node.id() == selected) {
-                self.ui_mode = UiMode::Normal;
-            }
+        let start = Instant::now();
+        self.anomalies = self
+            .process_watcher
+            .update_forest(&mut self.processes, self.group_by)
+            .len();
+        self.timings.build = start.elapsed();
+        if !self.marked.is_empty() {
+            // processes that are only hidden by the pattern, collapsing or
+            // grouping stay marked
+            let ids: HashSet<ProcessId> = self.processes.iter().map(Node::id).collect();
+            self.marked.retain(|id| ids.contains(id));
+            self.update_ui_mode();
         }
+        self.rows_outdated = true;
+        self.update_rows();
     }
-}
 
-impl tui_app::TuiApp for PorcApp {
-    fn update(&mut self, event: KeyEvent) -> R<UpdateResult> {
-        match (event.modifiers, self.ui_mode, event.code) {
-            (KeyModifiers::CONTROL, _, KeyCode::Char('c'))
-            | (KeyModifiers::NONE, UiMode::Normal, KeyCode::Char('q')) => {
-                return Ok(UpdateResult::Exit);
+    fn update_rows(&mut self) {
+        if !self.rows_outdated {
+            return;
+        }
+        let start = Instant::now();
+        let matches = |p: &Process| p.matches(&self.pattern);
+        let mut forest = match self.zoom {
+            Some(root) => self.processes.subtree(root).filtered(matches),
+            None => self.processes.filtered(matches),
+        };
+        if self.collapse_siblings {
+            forest.collapse_siblings(
+                &|parent, process| {
+                    process
+                        .collapsed_id(parent.map(Node::id))
+                        .filter(|id| !self.expanded.contains(id))
+                },
+                &Process::collapse,
+            );
+        }
+        forest.sort_by(&|a, b| Process::compare(a, b, &self.sorting));
+        let cursor = self.cursor_and_ancestors();
+        self.rows = forest.into_rows();
+        if let Some(index) = self.find_cursor(&cursor) {
+            self.list_state.select(Some(index));
+        }
+        self.rows_capacity = Capacity::max_of(self.rows.iter().map(|row| &row.node));
+        self.rows_outdated = false;
+        self.timings.filter = start.elapsed();
+    }
+
+    fn update_ui_mode(&mut self) {
+        if let UiMode::Normal | UiMode::ProcessSelected = self.ui_mode {
+            self.ui_mode = if self.marked.is_empty() {
+                UiMode::Normal
+            } else {
+                UiMode::ProcessSelected
+            };
+        }
+    }
+
+    fn perform(&mut self, action: Action) -> R<UpdateResult> {
+        match action {
+            Action::Quit => return Ok(UpdateResult::Exit),
+            Action::ToggleHelp => {
+                self.help_visible = !self.help_visible;
+                self.help_scroll = 0;
+            }
+            Action::ToggleDebugOverlay => {
+                self.debug_overlay_visible = !self.debug_overlay_visible;
             }
-            (KeyModifiers::NONE, _, KeyCode::Up) => {
-                self.list_state.select(Some(
-                    self.list_state.selected().unwrap_or(0).saturating_sub(1),
-                ));
-            }
-            (KeyModifiers::NONE, _, KeyCode::PageUp) => {
-                self.list_state.select(Some(
-                    self.list_state.selected().unwrap_or(0).saturating_sub(20),
-                ));
-            }
-            (KeyModifiers::NONE, _, KeyCode::Down) => {
-                self.list_state.select(Some(
-                    self.list_state.selected().unwrap_or(0).saturating_add(1),
-                ));
-            }
-            (KeyModifiers::NONE, _, KeyCode::PageDown) => {
-                self.list_state.select(Some(
-                    self.list_state.selected().unwrap_or(0).saturating_add(20),
-                ));
-            }
-            (KeyModifiers::NONE, UiMode::EditingPattern, KeyCode::Enter) => {
-                self.ui_mode = UiMode::Normal;
-            }
-            (KeyModifiers::NONE, _, KeyCode::Enter) => {
-                if let Some(selected) = self.list_state.selected() {
-                    if let Some(process) = self
-                        .forest
-                        .render_forest_prefixes()
-                        .into_iter()
-                        .nth(selected)
-                    {
-                        self.ui_mode = UiMode::ProcessSelected(process.1.id());
+            Action::Up => self.move_cursor_up(1),
+            Action::Down => self.move_cursor_down(1),
+            Action::PageUp => self.move_cursor_up(PAGE_SIZE),
+            Action::PageDown => self.move_cursor_down(PAGE_SIZE),
+            Action::HalfPageUp => self.move_cursor_up(self.half_page()),
+            Action::HalfPageDown => self.move_cursor_down(self.half_page()),
+            Action::Top => self.list_state.select(Some(0)),
+            Action::Bottom => self
+                .list_state
+                .select(Some(self.rows.len().saturating_sub(1))),
+            Action::SelectProcess => self.select_process(),
+            Action::ToggleMarked => {
+                if let Some(row) = self.row_under_cursor() {
+                    let ids = row.node.ids();
+                    if self.is_marked(&row.node) {
+                        for id in &ids {
+                            self.marked.remove(id);
+                        }
+                    } else {
+                        self.marked.extend(ids);
                     }
+                    self.move_cursor_down(1);
                 }
             }
-            (KeyModifiers::NONE, _, KeyCode::Char('/')) => {
-                self.ui_mode = UiMode::EditingPattern;
-            }
-            (KeyModifiers::NONE, _, KeyCode::Tab) => {
-                self.sort_column = self.sort_column.next();
-            }
-
-            // mode specific actions
-            (
-                KeyModifiers::NONE,
-                UiMode::EditingPattern | UiMode::ProcessSelected(_),
-                KeyCode::Esc,
-            ) => {
-                self.ui_mode = UiMode::Normal;
-            }
-            (KeyModifiers::NONE, UiMode::EditingPattern, KeyCode::Char(key)) if key.is_ascii() => {
-                self.pattern.modify(|pattern| pattern.push(key));
+            Action::MarkMatching => {
+                if !self.pattern.as_str().is_empty() {
+                    self.marked.extend(
+                        self.rows
+                            .iter()
+                            .filter(|row| row.node.matches(&self.pattern))
+                            .flat_map(|row| row.node.ids())
+                            .filter(|id| id.pid().is_some()),
+                    );
+                }
             }
-            (KeyModifiers::NONE, UiMode::EditingPattern, KeyCode::Backspace) => {
+            Action::Unselect => self.marked.clear(),
+            Action::StopEditingPattern => self.ui_mode = UiMode::Normal,
+            Action::StartEditingPattern => self.ui_mode = UiMode::EditingPattern,
+            Action::DeletePatternCharacter => {
                 self.pattern.modify(|pattern| {
                     pattern.pop();
                 });
+                self.rows_outdated = true;
+            }
+            Action::NextSortColumn => {
+                self.sorting.next(&self.columns);
+                self.rows_outdated = true;
+            }
+            Action::ReverseSortOrder => {
+                self.sorting.reverse();
+                self.rows_outdated = true;
+            }
+            Action::NextGrouping => {
+                self.group_by = self.group_by.next();
+                self.update_processes();
+            }
+            Action::ToggleCollapsing => {
+                self.collapse_siblings = !self.collapse_siblings;
+                self.rows_outdated = true;
+            }
+            Action::ToggleExpanded => self.toggle_expanded(),
+            Action::Sigterm => self
+                .for_marked(|watcher, process| watcher.send_signal(process.id(), Signal::SIGTERM)),
+            Action::Sigkill => self
+                .for_marked(|watcher, process| watcher.send_signal(process.id(), Signal::SIGKILL)),
+            Action::ToggleStopped => self.for_marked(|watcher, process| {
+                let signal = if process.stopped {
+                    Signal::SIGCONT
+                } else {
+                    Signal::SIGSTOP
+                };
+                watcher.send_signal(process.id(), signal)
+            }),
+            Action::Renice => self.prompt(Prompt::Nice),
+            Action::SetIoPriority => self.prompt(Prompt::IoPriority),
+            Action::SetAffinity => self.prompt(Prompt::Affinity),
+            Action::ApplyPrompt => {
+                if let UiMode::Prompting(prompt) = self.ui_mode {
+                    match prompt.parse(&self.input) {
+                        Ok(scheduling) => {
+                            self.for_marked(|watcher, process| {
+                                watcher.schedule(process.id(), &scheduling)
+                            });
+                            self.ui_mode = UiMode::Normal;
+                        }
+                        Err(error) => self.message = Some(error),
+                    }
+                }
             }
-            (KeyModifiers::NONE, UiMode::ProcessSelected(pid), KeyCode::Char('t')) => {
-                kill(
-                    nix::unistd::Pid::from_raw(pid.as_u32().try_into()?),
-                    nix::sys::signal::Signal::SIGTERM,
-                )?;
+            Action::CancelPrompt => self.ui_mode = UiMode::Normal,
+            Action::DeletePromptCharacter => {
+                self.input.pop();
             }
-            (KeyModifiers::NONE, UiMode::ProcessSelected(pid), KeyCode::Char('k')) => {
-                kill(
-                    nix::unistd::Pid::from_raw(pid.as_u32().try_into()?),
-                    nix::sys::signal::Signal::SIGKILL,
-                )?;
+            Action::Export => {
+                if !self.marked.is_empty() {
+                    self.exported = self
+                        .marked_processes()
+                        .filter_map(|process| {
+                            let pid = process.id().pid()?;
+                            Some(match process.arguments() {
+                                [] => format!("{}\t{}", pid, process.name()),
+                                arguments => format!("{}\t{}", pid, arguments.join(" ")),
+                            })
+                        })
+                        .collect();
+                    return Ok(UpdateResult::Exit);
+                }
             }
-            _ => {}
         }
-        self.update_processes();
+        self.update_ui_mode();
         Ok(UpdateResult::Continue)
     }
 
-    fn render(&mut self, area: Rect, buffer: &mut Buffer) {
-        let header_height = Process::render_header(area, self.sort_column, buffer);
+    /// Whether the row is marked, collapsed rows are when all their processes
+    /// are.
+    fn is_marked(&self, process: &Process) -> bool {
+        process.ids().iter().all(|id| self.marked.contains(id))
+    }
+
+    /// The marked processes, including the ones that are currently hidden.
+    fn marked_processes(&self) -> impl Iterator<Item = &Process> {
+        self.processes
+            .iter()
+            .filter(|process| self.marked.contains(&process.id()))
+    }
+
+    /// Tries all marked processes, even if some of them fail. Errors are
+    /// shown in the status bar instead of exiting porc, e.g. when renicing
+    /// another user's process.
+    fn for_marked(&mut self, f: impl Fn(&mut ProcessWatcher, &Process) -> R<()>) {
+        let mut errors = Vec::new();
+        for process in self.processes.iter() {
+            if self.marked.contains(&process.id()) {
+                if let Err(error) = f(&mut self.process_watcher, process) {
+                    let label = match process.id().pid() {
+                        Some(pid) => pid.to_string(),
+                        None => process.name().to_string(),
+                    };
+                    errors.push(format!("{}: {}", label, error));
+                }
+            }
+        }
+        self.message = match errors.as_slice() {
+            [] => None,
+            [error] => Some(error.clone()),
+            [error, rest @ ..] => Some(format!("{} (and {} more errors)", error, rest.len())),
+        };
+    }
+
+    fn prompt(&mut self, prompt: Prompt) {
+        if !self.marked.is_empty() {
+            self.input.clear();
+            self.ui_mode = UiMode::Prompting(prompt);
+        }
+    }
+
+    fn half_page(&self) -> usize {
+        usize::from(self.list_rect.height / 2).max(1)
+    }
+
+    fn move_cursor_up(&mut self, lines: usize) {
+        self.list_state.select(Some(
+            self.list_state
+                .selected()
+                .unwrap_or(0)
+                .saturating_sub(lines),
+        ));
+    }
+
+    fn move_cursor_down(&mut self, lines: usize) {
+        self.list_state.select(Some(
+            self.list_state
+                .selected()
+                .unwrap_or(0)
+                .saturating_add(lines),
+        ));
+    }
+
+    /// Moves the viewport of the list, keeping the cursor inside of it.
+    fn scroll_to(&mut self, offset: usize) {
+        let height = usize::from(self.list_rect.height);
+        let offset = offset.min(self.rows.len().saturating_sub(height));
+        *self.list_state.offset_mut() = offset;
+        if let Some(selected) = self.list_state.selected_mut() {
+            let last_visible = (offset + height).saturating_sub(1).max(offset);
+            *selected = (*selected).clamp(offset, last_visible);
+        }
+    }
+
+    fn status_bar_segments(&self) -> Vec<String> {
+        let mut segments = self.process_watcher.errors();
+        segments.extend(self.message.clone());
+        if let UiMode::Prompting(prompt) = self.ui_mode {
+            segments.push(format!("{}: {}▌", prompt.label(), self.input));
+        }
+        if self.marked.len() > 1 {
+            segments.push(format!("{} marked", self.marked.len()));
+        }
+        segments.extend(self.key_bindings.status_bar_segments(self.ui_mode.mode()));
+        match self.ui_mode {
+            UiMode::EditingPattern => {
+                segments.push(format!("type search pattern: {}▌", self.pattern.as_str()));
+            }
+            UiMode::Prompting(_) => {}
+            UiMode::Normal | UiMode::ProcessSelected => {
+                if !self.pattern.as_str().is_empty() {
+                    segments.push(format!("search pattern: {}", self.pattern.as_str()));
+                }
+            }
+        }
+        if self.group_by != GroupBy::Parent {
+            segments.push(format!("grouped by {}", self.group_by.description()));
+        }
+        segments
+    }
+
+    fn render_processes(&mut self, area: Rect, buffer: &mut Buffer) {
+        if area.width < MIN_WIDTH || area.height < MIN_HEIGHT {
+            Paragraph::new(format!(
+                "terminal too small: {}x{}, porc needs at least {}x{}",
+                area.width, area.height, MIN_WIDTH, MIN_HEIGHT
+            ))
+            .wrap(Wrap { trim: true })
+            .render(area, buffer);
+            self.header_rect = Rect::default();
+            self.list_rect = Rect::default();
+            return;
+        }
+        let header_height =
+            Process::render_header(area, &self.columns, &self.sorting, self.theme.lines, buffer);
+        self.header_rect = Rect {
+            height: header_height,
+            ..area
+        };
+        let status_bar = wrap_status_bar(
+            &self.status_bar_segments(),
+            area.width.into(),
+            usize::from(area.height - header_height - 1).min(MAX_STATUS_BAR_HEIGHT),
+        );
+        let status_bar_height = status_bar.len() as u16;
         let list_rect = Rect {
             x: area.x,
             y: area.y + header_height,
             width: area.width,
-            height: area.height - header_height - 1,
+            height: area.height - header_height - status_bar_height,
+        };
+        self.list_rect = list_rect;
+        normalize_list_state(&mut self.list_state, &self.rows, &list_rect);
+        let visible = scroll_to_selected(&mut self.list_state, self.rows.len(), &list_rect);
+        let capacity = match self.heat_map {
+            HeatMap::Off => None,
+            HeatMap::System => Some(self.process_watcher.capacity()),
+            HeatMap::View => Some(self.rows_capacity),
         };
-        let list = self.forest.render_forest_prefixes();
-        normalize_list_state(&mut self.list_state, &list, &list_rect);
-        let tree_lines = list.iter().enumerate().map(|(i, x)| {
+        let tree_lines = visible.map(|i| {
+            let row = &self.rows[i];
             let mut line = Line::default();
-            line.push_span(format!("{} ", x.1.table_data()));
-            line.push_span("┃".dark_gray());
+            for (j, column) in self.columns.iter().enumerate() {
+                if j > 0 {
+                    line.push_span(" ");
+                }
+                let cell = row.node.table_cell(*column);
+                line.push_span(match self.heat_color(&row.node, &[*column], capacity) {
+                    Some(color) => cell.fg(color),
+                    None => cell.into(),
+                });
+            }
+            line.push_span(" ");
+            line.push_span("┃".fg(self.theme.lines));
             line.push_span(if self.list_state.selected() == Some(i) {
                 " ▶ "
             } else {
                 "   "
             });
-            line.push_span(x.0.as_str().blue());
-            line.push_span(if self.ui_mode == UiMode::ProcessSelected(x.1.id()) {
-                x.1.to_string().reversed().red()
+            line.push_span(row.prefix(&self.rows).fg(self.theme.tree));
+            line.push_span(if self.is_marked(&row.node) {
+                row.node
+                    .to_string()
+                    .reversed()
+                    .fg(self.theme.selected_process)
             } else {
-                x.1.to_string().not_reversed()
+                match self.heat_color(&row.node, &self.columns, capacity) {
+                    Some(color) if self.tint_rows => row.node.to_string().not_reversed().fg(color),
+                    _ => row.node.to_string().not_reversed(),
+                }
             });
+            if row.node.stopped {
+                line.push_span(" [stopped]".fg(self.theme.stopped));
+            }
             line
         });
-        StatefulWidget::render(
-            List::new(tree_lines),
-            list_rect,
-            buffer,
-            &mut self.list_state,
-        );
+        Widget::render(List::new(tree_lines), list_rect, buffer);
         {
-            let status_bar = match self.ui_mode {
-                UiMode::Normal => {
-                    let mut commands = vec![
-                        "Ctrl+C: Quit".to_string(),
-                        "↑↓ : scroll".to_string(),
-                        "ENTER: select process".to_string(),
-                        "/: filter processes".to_string(),
-                    ];
-                    if !self.pattern.as_str().is_empty() {
-                        commands.push(format!("search pattern: {}", self.pattern.as_str()));
-                    }
-                    commands.join(" | ")
-                }
-                UiMode::EditingPattern => [
-                    "Ctrl+C: Quit",
-                    "↑↓ : scroll",
-                    "ENTER: select process",
-                    "ESC: exit search mode",
-                    &format!("type search pattern: {}▌", self.pattern.as_str()),
-                ]
-                .join(" | "),
-                UiMode::ProcessSelected(_pid) => {
-                    let mut commands = vec![
-                        "Ctrl+C: Quit".to_string(),
-                        "↑↓ : scroll".to_string(),
-                        "t: SIGTERM process".to_string(),
-                        "k: SIGKILL process".to_string(),
-                        "ESC: unselect".to_string(),
-                        "ENTER: select other".to_string(),
-                    ];
-                    if !self.pattern.as_str().is_empty() {
-                        commands.push(format!("search pattern: {}", self.pattern.as_str()));
-                    }
-                    commands.join(" | ")
-                }
-            };
-            let mut status_bar = Paragraph::new(status_bar).reversed();
-            match self.ui_mode {
-                UiMode::Normal => {}
-                UiMode::EditingPattern => {
-                    status_bar = status_bar.yellow();
-                }
-                UiMode::ProcessSelected(_) => {
-                    status_bar = status_bar.red();
-                }
-            }
+            let status_bar =
+                Paragraph::new(status_bar.join("\n"))
+                    .reversed()
+                    .fg(match self.ui_mode {
+                        UiMode::Normal => self.theme.status_bar,
+                        UiMode::EditingPattern => self.theme.status_bar_editing,
+                        UiMode::ProcessSelected | UiMode::Prompting(_) => {
+                            self.theme.status_bar_selected
+                        }
+                    });
             status_bar.render(
                 Rect {
                     x: area.x,
-                    y: area.height - 1,
+                    y: area.bottom() - status_bar_height,
                     width: area.width,
-                    height: 1,
+                    height: status_bar_height,
                 },
                 buffer,
             );
         }
+        if self.help_visible {
+            self.render_help(area, buffer);
+        }
+    }
+
+    fn render_help(&mut self, area: Rect, buffer: &mut Buffer) {
+        let bindings: Vec<(Mode, Vec<(Action, String)>)> = Mode::all()
+            .map(|mode| {
+                let actions = self
+                    .key_bindings
+                    .for_mode(mode)
+                    .into_iter()
+                    .map(|(action, keys)| (action, keys.join(", ")))
+                    .collect();
+                (mode, actions)
+            })
+            .collect();
+        let keys_width = bindings
+            .iter()
+            .flat_map(|(_, actions)| actions.iter().map(|(_, keys)| keys.chars().count()))
+            .max()
+            .unwrap_or(0);
+        let mut lines = Vec::new();
+        for (mode, actions) in bindings {
+            if !lines.is_empty() {
+                lines.push(Line::default());
+            }
+            lines.push(Line::from(mode.description()).bold());
+            for (action, keys) in actions {
+                lines.push(Line::from(format!(
+                    "  {:<width$}  {}",
+                    keys,
+                    action.description(),
+                    width = keys_width
+                )));
+            }
+        }
+        let title = " key bindings (↑↓ to scroll, other keys close) ";
+        let width = (lines.iter().map(Line::width).max().unwrap_or(0) + 2)
+            .max(title.chars().count() + 2)
+            .min(area.width.into()) as u16;
+        let height = (lines.len() + 2).min(area.height.into()) as u16;
+        self.help_height = usize::from(height.saturating_sub(2));
+        self.help_scroll = self
+            .help_scroll
+            .min(lines.len().saturating_sub(self.help_height));
+        let popup = Rect {
+            x: area.x + (area.width - width) / 2,
+            y: area.y + (area.height - height) / 2,
+            width,
+            height,
+        };
+        Clear.render(popup, buffer);
+        Paragraph::new(lines)
+            .block(Block::bordered().title(title))
+            .scroll((self.help_scroll.try_into().unwrap_or(u16::MAX), 0))
+            .render(popup, buffer);
+    }
+
+    fn render_debug_overlay(&self, area: Rect, buffer: &mut Buffer) {
+        let lines: Vec<Line> = [
+            ("refresh", format_duration(self.timings.refresh)),
+            ("build", format_duration(self.timings.build)),
+            ("filter", format_duration(self.timings.filter)),
+            ("render", format_duration(self.timings.render)),
+            ("rows", self.rows.len().to_string()),
+            ("anomalies", self.anomalies.to_string()),
+        ]
+        .into_iter()
+        .map(|(label, value)| Line::from(format!("{:<10}{:>9}", label, value)))
+        .collect();
+        let width = (19 + 2).min(area.width);
+        let height = (lines.len() as u16 + 2).min(area.height);
+        let overlay = Rect {
+            x: area.right() - width,
+            y: area.y,
+            width,
+            height,
+        };
+        Clear.render(overlay, buffer);
+        Paragraph::new(lines)
+            .block(Block::bordered().title(" debug "))
+            .render(overlay, buffer);
+    }
+
+    /// The heat map color for the highest load in the given columns.
+    fn heat_color(
+        &self,
+        process: &Process,
+        columns: &[Column],
+        capacity: Option<Capacity>,
+    ) -> Option<Color> {
+        let capacity = capacity?;
+        let load = columns
+            .iter()
+            .filter_map(|column| process.load(*column, capacity))
+            .reduce(f32::max)?;
+        self.theme.heat_color(load)
+    }
+
+    fn row_under_cursor(&self) -> Option<&Row<Process>> {
+        self.rows.get(self.list_state.selected()?)
+    }
+
+    /// The ids of the process under the cursor and of its ancestors, so that
+    /// the cursor can stay on that process, or the nearest ancestor if it
+    /// exits, when the rows change. When collapsing siblings, the id of the
+    /// row the process would get collapsed into comes second.
+    fn cursor_and_ancestors(&self) -> Vec<ProcessId> {
+        let index = self
+            .list_state
+            .selected()
+            .map(|index| index.min(self.rows.len().saturating_sub(1)));
+        let mut result = Vec::new();
+        let mut row = index.and_then(|index| self.rows.get(index));
+        while let Some(current) = row {
+            result.push(current.node.id());
+            if self.collapse_siblings && result.len() == 1 {
+                result.extend(current.node.collapsed_id(current.parent));
+            }
+            row = current.parent_row().and_then(|index| self.rows.get(index));
+        }
+        result
+    }
+
+    fn find_cursor(&self, cursor: &[ProcessId]) -> Option<usize> {
+        let position = |id: ProcessId| self.rows.iter().position(|row| row.node.id() == id);
+        let (&first, rest) = cursor.split_first()?;
+        position(first)
+            .or_else(|| {
+                // the cursor was on a collapsed row that got expanded
+                self.collapse_siblings
+                    .then(|| {
+                        self.rows
+                            .iter()
+                            .position(|row| row.node.collapsed_id(row.parent) == Some(first))
+                    })
+                    .flatten()
+            })
+            .or_else(|| rest.iter().find_map(|id| position(*id)))
+    }
+
+    fn select_process(&mut self) {
+        if let Some(row) = self.row_under_cursor() {
+            self.marked.extend(row.node.ids());
+            self.update_ui_mode();
+        }
+    }
+
+    /// Expands a collapsed row, or collapses the siblings of an expanded one.
+    fn toggle_expanded(&mut self) {
+        let Some(row) = self.row_under_cursor() else {
+            return;
+        };
+        if row.node.count > 1 {
+            self.expanded.insert(row.node.id());
+        } else if let Some(id) = row.node.collapsed_id(row.parent) {
+            self.expanded.remove(&id);
+        }
+        self.rows_outdated = true;
+    }
+
+    fn click(&mut self, column: u16, row: u16) {
+        if self.header_rect.contains((column, row).into()) {
+            if let Some(sort_column) =
+                Process::header_column_at(self.header_rect, &self.columns, column)
+            {
+                self.sorting.select(sort_column);
+                self.rows_outdated = true;
+            }
+        } else if self.list_rect.contains((column, row).into()) {
+            let index = self.list_state.offset() + usize::from(row - self.list_rect.y);
+            if index >= self.rows.len() {
+                return;
+            }
+            self.list_state.select(Some(index));
+            match self.last_click {
+                Some((time, last_index))
+                    if last_index == index && time.elapsed() < DOUBLE_CLICK_INTERVAL =>
+                {
+                    self.select_process();
+                    self.last_click = None;
+                }
+                _ => {
+                    self.last_click = Some((Instant::now(), index));
+                }
+            }
+        }
+    }
+}
+
+impl tui_app::TuiApp for PorcApp {
+    fn update(&mut self, event: KeyEvent) -> R<UpdateResult> {
+        let action = self.key_bindings.lookup(self.ui_mode.mode(), &event);
+        if self.help_visible {
+            // scrolling gets clamped when rendering
+            match action {
+                Some(Action::Quit) => return Ok(UpdateResult::Exit),
+                Some(Action::Up) => self.help_scroll = self.help_scroll.saturating_sub(1),
+                Some(Action::Down) => self.help_scroll = self.help_scroll.saturating_add(1),
+                Some(Action::PageUp | Action::HalfPageUp) => {
+                    self.help_scroll = self.help_scroll.saturating_sub(self.help_height)
+                }
+                Some(Action::PageDown | Action::HalfPageDown) => {
+                    self.help_scroll = self.help_scroll.saturating_add(self.help_height)
+                }
+                Some(Action::Top) => self.help_scroll = 0,
+                Some(Action::Bottom) => self.help_scroll = usize::MAX,
+                _ => self.help_visible = false,
+            }
+            return Ok(UpdateResult::Continue);
+        }
+        self.message = None;
+        match action {
+            Some(action) => {
+                if let UpdateResult::Exit = self.perform(action)? {
+                    return Ok(UpdateResult::Exit);
+                }
+            }
+            None => match (event.modifiers, self.ui_mode, event.code) {
+                (KeyModifiers::NONE, UiMode::EditingPattern, KeyCode::Char(key))
+                    if key.is_ascii() =>
+                {
+                    self.pattern.modify(|pattern| pattern.push(key));
+                    self.rows_outdated = true;
+                }
+                (
+                    KeyModifiers::NONE | KeyModifiers::SHIFT,
+                    UiMode::Prompting(_),
+                    KeyCode::Char(key),
+                ) if key.is_ascii() => {
+                    self.input.push(key);
+                }
+                _ => {}
+            },
+        }
+        self.update_rows();
+        Ok(UpdateResult::Continue)
+    }
+
+    fn update_mouse(&mut self, event: MouseEvent) -> R<UpdateResult> {
+        match event.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                self.click(event.column, event.row);
+            }
+            MouseEventKind::ScrollUp => {
+                self.scroll_to(self.list_state.offset().saturating_sub(MOUSE_SCROLL_LINES))
+            }
+            MouseEventKind::ScrollDown => {
+                self.scroll_to(self.list_state.offset().saturating_add(MOUSE_SCROLL_LINES))
+            }
+            _ => return Ok(UpdateResult::Continue),
+        }
+        self.update_rows();
+        Ok(UpdateResult::Continue)
+    }
+
+    fn render(&mut self, area: Rect, buffer: &mut Buffer) {
+        let start = Instant::now();
+        self.render_processes(area, buffer);
+        if self.debug_overlay_visible {
+            self.render_debug_overlay(area, buffer);
+        }
+        self.timings.render = start.elapsed();
     }
 
-    fn tick(&mut self) {
-        self.process_watcher.refresh();
+    fn tick(&mut self) -> R<UpdateResult> {
+        if let Some(command) = &mut self.command {
+            if command.has_exited()? {
+                return Ok(UpdateResult::Exit);
+            }
+        }
+        let start = Instant::now();
+        self.process_watcher.refresh()?;
+        self.timings.refresh = start.elapsed();
         self.update_processes();
+        Ok(UpdateResult::Continue)
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    format!("{:.1}ms", duration.as_secs_f64() * 1000.0)
+}
+
+fn wrap_status_bar(segments: &[String], width: usize, max_lines: usize) -> Vec<String> {
+    const SEPARATOR: &str = " | ";
+    let mut lines: Vec<String> = Vec::new();
+    for segment in segments {
+        match lines.last_mut() {
+            Some(line)
+                if line.chars().count() + SEPARATOR.len() + segment.chars().count() <= width =>
+            {
+                line.push_str(SEPARATOR);
+                line.push_str(segment);
+            }
+            _ => lines.push(segment.clone()),
+        }
+    }
+    if lines.len() > max_lines {
+        lines.truncate(max_lines);
+        if let Some(last) = lines.last_mut() {
+            last.push_str(" …");
+        }
+    }
+    lines
+        .into_iter()
+        .map(|line| truncate(line, width))
+        .collect()
+}
+
+fn truncate(line: String, width: usize) -> String {
+    if line.chars().count() <= width {
+        line
+    } else {
+        let mut result: String = line.chars().take(width.saturating_sub(1)).collect();
+        result.push('…');
+        result
     }
 }
 
+/// Moves the offset of the list so that the selected row is visible, like
+/// ratatui's `List` does, and returns the rows that fit into `rect`.
+fn scroll_to_selected(list_state: &mut ListState, length: usize, rect: &Rect) -> Range<usize> {
+    let height = usize::from(rect.height);
+    let mut offset = list_state.offset();
+    if let Some(selected) = list_state.selected() {
+        if selected < offset {
+            offset = selected;
+        } else if height > 0 && selected >= offset + height {
+            offset = selected + 1 - height;
+        }
+    }
+    *list_state.offset_mut() = offset;
+    offset..(offset + height).min(length)
+}
+
 fn normalize_list_state<T>(list_state: &mut ListState, list: &[T], rect: &Rect) {
     if let Some(ref mut selected) = list_state.selected_mut() {
         *selected = (*selected).min(list.len().saturating_sub(1));
@@ -257,13 +940,18 @@ fn normalize_list_state<T>(list_state: &mut ListState, list: &[T], rect: &Rect)
 }
 
 #[cfg(test)]
+#[allow(clippy::useless_vec)]
 mod test {
     use super::*;
+    use crate::process::SortColumn;
+    use crate::regex::FilterMode;
+    use crate::theme::BuiltinTheme;
     use crate::tui_app::TuiApp;
     use crossterm::event::{KeyEventKind, KeyEventState};
     use insta::assert_snapshot;
     use ratatui::buffer::Cell;
     use ratatui::layout::Rect;
+    use ratatui::style::Color;
     use ratatui::style::Modifier;
     use ratatui::widgets::ListState;
 
@@ -303,14 +991,71 @@ mod test {
         assert_eq!(list_state.offset(), 10);
     }
 
+    #[test]
+    fn scrolling_down_to_the_selected_row() {
+        let mut list_state = ListState::default().with_selected(Some(25));
+        assert_eq!(scroll_to_selected(&mut list_state, 30, &RECT), 6..26);
+        assert_eq!(list_state.offset(), 6);
+    }
+
+    #[test]
+    fn scrolling_up_to_the_selected_row() {
+        let mut list_state = ListState::default().with_selected(Some(3)).with_offset(10);
+        assert_eq!(scroll_to_selected(&mut list_state, 30, &RECT), 3..23);
+    }
+
+    #[test]
+    fn scrolling_stops_at_the_end_of_the_list() {
+        let mut list_state = ListState::default().with_selected(Some(2)).with_offset(1);
+        assert_eq!(scroll_to_selected(&mut list_state, 5, &RECT), 1..5);
+    }
+
+    #[test]
+    fn status_bar_segments_get_joined_when_they_fit() {
+        let segments = vec!["a: foo".to_string(), "b: bar".to_string()];
+        assert_eq!(wrap_status_bar(&segments, 20, 2), vec!["a: foo | b: bar"]);
+    }
+
+    #[test]
+    fn status_bar_segments_get_wrapped_when_they_dont_fit() {
+        let segments = vec!["a: foo".to_string(), "b: bar".to_string()];
+        assert_eq!(wrap_status_bar(&segments, 10, 2), vec!["a: foo", "b: bar"]);
+    }
+
+    #[test]
+    fn status_bar_gets_truncated_after_the_maximum_number_of_lines() {
+        let segments = vec![
+            "a: foo".to_string(),
+            "b: bar".to_string(),
+            "c: baz".to_string(),
+        ];
+        assert_eq!(
+            wrap_status_bar(&segments, 10, 2),
+            vec!["a: foo", "b: bar …"]
+        );
+    }
+
+    #[test]
+    fn status_bar_truncates_segments_that_are_too_long() {
+        let segments = vec!["search pattern: foobar".to_string()];
+        assert_eq!(wrap_status_bar(&segments, 10, 2), vec!["search pa…"]);
+    }
+
     fn test_app(processes: Vec<Process>) -> R<PorcApp> {
-        let mut app = PorcApp::new(ProcessWatcher::fake(processes), None)?;
-        app.tick();
+        test_app_with_settings(processes, Settings::default())
+    }
+
+    fn test_app_with_settings(processes: Vec<Process>, settings: Settings) -> R<PorcApp> {
+        let mut app = PorcApp::new(ProcessWatcher::fake(processes), settings)?;
+        app.tick()?;
         Ok(app)
     }
 
-    fn render_ui(mut app: PorcApp) -> String {
-        let area = Rect::new(0, 0, 80, 10);
+    fn render_ui(app: PorcApp) -> String {
+        render_ui_with_area(app, Rect::new(0, 0, 80, 10))
+    }
+
+    fn render_ui_with_area(mut app: PorcApp, area: Rect) -> String {
         let mut buffer = Buffer::filled(area, Cell::new(" "));
         app.render(area, &mut buffer);
         let mut result = String::new();
@@ -338,8 +1083,33 @@ mod test {
         })
     }
 
+    fn simulate_mouse(app: &mut PorcApp, kind: MouseEventKind, column: u16, row: u16) -> R<()> {
+        simulate_mouse_in(app, Rect::new(0, 0, 80, 10), kind, column, row)
+    }
+
+    fn simulate_mouse_in(
+        app: &mut PorcApp,
+        area: Rect,
+        kind: MouseEventKind,
+        column: u16,
+        row: u16,
+    ) -> R<()> {
+        app.render(area, &mut Buffer::empty(area));
+        app.update_mouse(MouseEvent {
+            kind,
+            column,
+            row,
+            modifiers: KeyModifiers::NONE,
+        })?;
+        Ok(())
+    }
+
+    fn simulate_click(app: &mut PorcApp, column: u16, row: u16) -> R<()> {
+        simulate_mouse(app, MouseEventKind::Down(MouseButton::Left), column, row)
+    }
+
     fn set_pattern(app: &mut PorcApp, pattern: &str) -> R<()> {
-        app.pattern = crate::regex::Regex::new(::regex::Regex::new(pattern)?);
+        app.pattern = Regex::parse(pattern, FilterMode::Regex)?;
         Ok(())
     }
 
@@ -357,37 +1127,679 @@ mod test {
     }
 
     #[test]
-    fn processes_get_sorted_by_pid() -> R<()> {
-        let app = test_app(vec![
-            Process::fake(1, 1.0, None),
-            Process::fake(2, 2.0, None),
-            Process::fake(3, 4.0, None),
-            Process::fake(4, 3.0, None),
-        ])?;
+    fn shows_the_configured_columns() -> R<()> {
+        let app = test_app_with_settings(
+            vec![Process::fake(1, 4.0, None), Process::fake(2, 3.0, Some(1))],
+            Settings {
+                columns: vec![Column::Cpu, Column::Pid],
+                ..Settings::default()
+            },
+        )?;
         assert_snapshot!(render_ui(app));
         Ok(())
     }
 
     #[test]
-    fn processes_can_be_sorted_by_cpu() -> R<()> {
-        let mut app = test_app(vec![
-            Process::fake(1, 1.0, None),
-            Process::fake(2, 2.0, None),
-            Process::fake(3, 4.0, None),
-            Process::fake(4, 3.0, None),
-        ])?;
+    fn sort_column_cycles_through_the_configured_columns() -> R<()> {
+        let mut app = test_app_with_settings(
+            vec![Process::fake(1, 0.0, None)],
+            Settings {
+                columns: vec![Column::Ram, Column::Pid],
+                sort: Sorting::new(Column::Ram),
+                ..Settings::default()
+            },
+        )?;
         simulate_key_press(&mut app, KeyCode::Tab)?;
-        assert_snapshot!(render_ui(app));
+        assert_eq!(
+            app.sorting.primary().column,
+            SortColumn::Column(Column::Pid)
+        );
+        simulate_key_press(&mut app, KeyCode::Tab)?;
+        assert_eq!(app.sorting.primary().column, SortColumn::Name);
+        simulate_key_press(&mut app, KeyCode::Tab)?;
+        assert_eq!(
+            app.sorting.primary().column,
+            SortColumn::Column(Column::Ram)
+        );
         Ok(())
     }
 
     #[test]
-    fn more_complicated_tree() -> R<()> {
-        let app = test_app(vec![
-            Process::fake(1, 1.0, None),
-            Process::fake(2, 2.0, Some(1)),
-            Process::fake(3, 3.0, Some(2)),
-            Process::fake(4, 4.0, Some(1)),
+    fn invalid_patterns_in_the_settings_are_reported() -> R<()> {
+        let app = test_app_with_settings(
+            vec![Process::fake(1, 0.0, None)],
+            Settings {
+                pattern: "(".to_string(),
+                ..Settings::default()
+            },
+        )?;
+        assert_eq!(app.pattern.as_str(), "(");
+        assert_eq!(app.message.as_deref(), Some("invalid search pattern: ("));
+        Ok(())
+    }
+
+    #[test]
+    fn literal_filter_mode() -> R<()> {
+        let app = test_app_with_settings(
+            vec![Process::fake(1, 0.0, None), Process::fake(2, 0.0, None)],
+            Settings {
+                pattern: ".".to_string(),
+                filter_mode: FilterMode::Literal,
+                ..Settings::default()
+            },
+        )?;
+        assert_eq!(app.rows.len(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn colors_can_be_disabled() -> R<()> {
+        let mut app = test_app_with_settings(
+            vec![Process::fake(1, 0.0, None)],
+            Settings {
+                theme: BuiltinTheme::Monochrome.theme(),
+                ..Settings::default()
+            },
+        )?;
+        let area = Rect::new(0, 0, 80, 10);
+        let mut buffer = Buffer::empty(area);
+        app.render(area, &mut buffer);
+        assert!(buffer
+            .content()
+            .iter()
+            .all(|cell| cell.fg == Color::Reset && cell.bg == Color::Reset));
+        Ok(())
+    }
+
+    #[test]
+    fn grouping_by_container() -> R<()> {
+        let mut app = test_app_with_settings(
+            vec![
+                Process::fake(1, 1.0, None),
+                Process::fake(2, 1.0, Some(1)),
+                Process::fake(3, 2.0, Some(2)).with_container("a"),
+                Process::fake(4, 3.0, Some(3)).with_container("a"),
+                Process::fake(5, 4.0, Some(2)).with_container("b"),
+            ],
+            Settings {
+                columns: vec![Column::Pid, Column::Cpu, Column::Container],
+                ..Settings::default()
+            },
+        )?;
+        simulate_key_press(&mut app, KeyCode::Char('g'))?;
+        assert_eq!(app.group_by, GroupBy::Container);
+        assert_snapshot!(render_ui(app));
+        Ok(())
+    }
+
+    #[test]
+    fn grouping_by_systemd_unit() -> R<()> {
+        let app = test_app_with_settings(
+            vec![
+                Process::fake(1, 1.0, None).with_cgroup("/init.scope"),
+                Process::fake(2, 2.0, Some(1)).with_cgroup("/system.slice/nginx.service"),
+                Process::fake(3, 3.0, Some(2)).with_cgroup("/system.slice/nginx.service"),
+                Process::fake(4, 4.0, Some(1)).with_cgroup("/system.slice/cron.service"),
+            ],
+            Settings {
+                columns: vec![Column::Pid, Column::Cpu, Column::Unit],
+                group_by: GroupBy::Unit,
+                ..Settings::default()
+            },
+        )?;
+        assert_snapshot!(render_ui(app));
+        Ok(())
+    }
+
+    #[test]
+    fn grouping_by_user_shows_totals_per_user() -> R<()> {
+        let app = test_app_with_settings(
+            vec![
+                Process::fake(1, 1.0, None).with_user("root"),
+                Process::fake(2, 20.0, Some(1)).with_user("alice"),
+                Process::fake(3, 30.0, Some(2)).with_user("alice"),
+                Process::fake(4, 4.0, Some(1)).with_user("bob"),
+            ],
+            Settings {
+                columns: vec![Column::Pid, Column::Cpu, Column::User],
+                group_by: GroupBy::User,
+                ..Settings::default()
+            },
+        )?;
+        assert_snapshot!(render_ui(app));
+        Ok(())
+    }
+
+    mod collapsing {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        fn worker(pid: usize, cpu: f32) -> Process {
+            let mut process = Process::fake(pid, cpu, Some(1));
+            process.name = "worker".to_string();
+            process
+        }
+
+        fn test_app() -> R<PorcApp> {
+            test_app_with_settings(
+                vec![
+                    Process::fake(1, 1.0, None),
+                    worker(2, 2.0),
+                    worker(3, 3.0),
+                    worker(4, 4.0),
+                    Process::fake(5, 20.0, Some(1)),
+                ],
+                Settings {
+                    collapse_siblings: true,
+                    ..Settings::default()
+                },
+            )
+        }
+
+        fn rows(app: &PorcApp) -> Vec<String> {
+            app.rows
+                .iter()
+                .map(|row| format!("{}{}", row.prefix(&app.rows), row.node))
+                .collect()
+        }
+
+        #[test]
+        fn merges_siblings_with_the_same_name() -> R<()> {
+            let app = test_app()?;
+            assert_snapshot!(render_ui(app));
+            Ok(())
+        }
+
+        #[test]
+        fn collapsed_rows_can_be_expanded_and_collapsed_again() -> R<()> {
+            let mut app = test_app()?;
+            simulate_key_press(&mut app, KeyCode::Down)?;
+            simulate_key_press(&mut app, KeyCode::Down)?;
+            simulate_key_press(&mut app, KeyCode::Char('e'))?;
+            assert_eq!(
+                rows(&app),
+                vec!["one", "├── worker", "├── worker", "├── worker", "└── five"]
+            );
+            simulate_key_press(&mut app, KeyCode::Char('e'))?;
+            assert_eq!(rows(&app), vec!["one", "├── five", "└── worker ×3"]);
+            Ok(())
+        }
+
+        #[test]
+        fn can_be_toggled() -> R<()> {
+            let mut app = test_app()?;
+            simulate_key_press(&mut app, KeyCode::Char('c'))?;
+            assert_eq!(rows(&app).len(), 5);
+            Ok(())
+        }
+
+        #[test]
+        fn marking_collapsed_rows_marks_all_their_processes() -> R<()> {
+            let mut app = test_app()?;
+            simulate_key_press(&mut app, KeyCode::End)?;
+            simulate_key_press(&mut app, KeyCode::Char(' '))?;
+            assert_eq!(app.marked, HashSet::from([2.into(), 3.into(), 4.into()]));
+            simulate_key_press(&mut app, KeyCode::End)?;
+            simulate_key_press(&mut app, KeyCode::Char(' '))?;
+            assert!(app.marked.is_empty());
+            simulate_key_press(&mut app, KeyCode::End)?;
+            simulate_key_press(&mut app, KeyCode::Char(' '))?;
+            simulate_key_press(&mut app, KeyCode::Char('t'))?;
+            assert_eq!(
+                app.process_watcher.sent_signals(),
+                &[
+                    (2.into(), Signal::SIGTERM),
+                    (3.into(), Signal::SIGTERM),
+                    (4.into(), Signal::SIGTERM)
+                ]
+            );
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn signals_to_group_nodes_are_refused() -> R<()> {
+        let mut app = test_app_with_settings(
+            vec![Process::fake(1, 0.0, None).with_container("a")],
+            Settings {
+                group_by: GroupBy::Container,
+                ..Settings::default()
+            },
+        )?;
+        simulate_key_press(&mut app, KeyCode::Enter)?;
+        assert_eq!(app.ui_mode, UiMode::ProcessSelected);
+        assert!(matches!(
+            app.marked.iter().collect::<Vec<_>>()[..],
+            [ProcessId::Group(_)]
+        ));
+        simulate_key_press(&mut app, KeyCode::Char('t'))?;
+        assert_eq!(app.process_watcher.sent_signals(), &[]);
+        assert_eq!(
+            app.message.as_deref(),
+            Some("docker:a: groups cannot be signalled")
+        );
+        Ok(())
+    }
+
+    mod cursor {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        fn process_under_cursor(app: &PorcApp) -> Option<String> {
+            app.row_under_cursor().map(|row| row.node.name.clone())
+        }
+
+        #[test]
+        fn stays_on_its_process_when_processes_appear_above() -> R<()> {
+            let mut app = test_app(vec![
+                Process::fake(2, 0.0, None),
+                Process::fake(3, 0.0, Some(2)),
+            ])?;
+            simulate_key_press(&mut app, KeyCode::Down)?;
+            app.process_watcher.set_processes(vec![
+                Process::fake(1, 0.0, None),
+                Process::fake(2, 0.0, None),
+                Process::fake(3, 0.0, Some(2)),
+            ]);
+            app.tick()?;
+            assert_eq!(process_under_cursor(&app), Some("three".to_string()));
+            assert_eq!(app.list_state.selected(), Some(2));
+            Ok(())
+        }
+
+        #[test]
+        fn moves_to_the_nearest_surviving_ancestor_when_its_process_exits() -> R<()> {
+            let mut app = test_app(vec![
+                Process::fake(1, 0.0, None),
+                Process::fake(2, 0.0, Some(1)),
+                Process::fake(3, 0.0, Some(2)),
+                Process::fake(4, 0.0, Some(3)),
+                Process::fake(5, 0.0, None),
+            ])?;
+            simulate_key_press(&mut app, KeyCode::Down)?;
+            simulate_key_press(&mut app, KeyCode::Down)?;
+            simulate_key_press(&mut app, KeyCode::Down)?;
+            assert_eq!(process_under_cursor(&app), Some("four".to_string()));
+            app.process_watcher.set_processes(vec![
+                Process::fake(1, 0.0, None),
+                Process::fake(2, 0.0, Some(1)),
+                Process::fake(5, 0.0, None),
+            ]);
+            app.tick()?;
+            assert_eq!(process_under_cursor(&app), Some("two".to_string()));
+            Ok(())
+        }
+
+        #[test]
+        fn follows_its_process_when_filtering() -> R<()> {
+            let mut app = test_app((1..=4).map(|pid| Process::fake(pid, 0.0, None)).collect())?;
+            simulate_key_press(&mut app, KeyCode::End)?;
+            simulate_key_press(&mut app, KeyCode::Char('/'))?;
+            simulate_key_press(&mut app, KeyCode::Char('f'))?;
+            simulate_key_press(&mut app, KeyCode::Backspace)?;
+            assert_eq!(app.list_state.selected(), Some(3));
+            simulate_key_press(&mut app, KeyCode::Char('f'))?;
+            assert_eq!(process_under_cursor(&app), Some("four".to_string()));
+            assert_eq!(app.list_state.selected(), Some(0));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn scrolling_to_the_end_of_a_big_tree() -> R<()> {
+        let mut processes = vec![Process::fake(1, 0.0, None)];
+        processes.extend(
+            (2..=20_000)
+                .map(|pid| Process::fake_named(pid, &format!("worker-{pid}"), 0.0, Some(1))),
+        );
+        let mut app = test_app(processes)?;
+        simulate_key_press(&mut app, KeyCode::End)?;
+        assert_snapshot!(render_ui(app));
+        Ok(())
+    }
+
+    #[test]
+    fn refreshing_applies_new_and_exited_processes() -> R<()> {
+        let mut app = test_app(vec![
+            Process::fake(1, 1.0, None),
+            Process::fake(2, 2.0, Some(1)),
+            Process::fake(3, 3.0, Some(1)),
+        ])?;
+        app.process_watcher.set_processes(vec![
+            Process::fake(1, 1.0, None),
+            Process::fake(3, 3.0, Some(1)),
+            Process::fake(4, 4.0, Some(3)),
+        ]);
+        app.tick()?;
+        let rows: Vec<(String, String)> = app
+            .rows
+            .iter()
+            .map(|row| {
+                (
+                    format!("{}{}", row.prefix(&app.rows), row.node),
+                    row.node.table_cell(Column::Cpu).trim().to_string(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                ("one".to_string(), "8%".to_string()),
+                ("└─┬ three".to_string(), "7%".to_string()),
+                ("  └── four".to_string(), "4%".to_string()),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn zooming_to_a_subtree() -> R<()> {
+        let mut app = test_app(vec![
+            Process::fake(1, 0.0, None),
+            Process::fake(2, 0.0, Some(1)),
+            Process::fake(3, 0.0, Some(2)),
+            Process::fake(4, 0.0, Some(1)),
+        ])?;
+        app.zoom = Some(2.into());
+        app.rows_outdated = true;
+        app.update_rows();
+        assert_eq!(sorted_pids(&app), vec![2, 3]);
+        Ok(())
+    }
+
+    #[test]
+    fn exits_when_the_launched_command_exits() -> R<()> {
+        let mut app = test_app(vec![])?;
+        app.launch(LaunchedCommand::spawn(&["true".to_string()])?);
+        let start = Instant::now();
+        while let UpdateResult::Continue = app.tick()? {
+            assert!(start.elapsed() < Duration::from_secs(10));
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(
+            app.take_command()
+                .map(LaunchedCommand::finish)
+                .transpose()?,
+            Some(0)
+        );
+        Ok(())
+    }
+
+    mod several_hosts {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        fn test_app() -> R<(PorcApp, Vec<std::thread::JoinHandle<ProcessWatcher>>)> {
+            let (process_watcher, agents) = ProcessWatcher::fake_hosts(vec![
+                (
+                    "build-1",
+                    vec![
+                        Process::fake(1, 10.0, None),
+                        Process::fake(2, 20.0, Some(1)),
+                    ],
+                ),
+                ("build-2", vec![Process::fake(1, 30.0, None)]),
+            ])?;
+            let mut app = PorcApp::new(process_watcher, Settings::default())?;
+            app.update_processes();
+            Ok((app, agents))
+        }
+
+        #[test]
+        fn every_host_gets_a_root() -> R<()> {
+            let (app, _agents) = test_app()?;
+            assert_snapshot!(render_ui(app));
+            Ok(())
+        }
+
+        #[test]
+        fn signals_go_to_the_right_host() -> R<()> {
+            let (mut app, agents) = test_app()?;
+            simulate_key_press(&mut app, KeyCode::End)?;
+            simulate_key_press(&mut app, KeyCode::Enter)?;
+            simulate_key_press(&mut app, KeyCode::Char('t'))?;
+            drop(app);
+            let signals: Vec<Vec<(sysinfo::Pid, Signal)>> = agents
+                .into_iter()
+                .map(|agent| agent.join().unwrap().sent_signals().to_vec())
+                .collect();
+            assert_eq!(signals, vec![vec![], vec![(1.into(), Signal::SIGTERM)]]);
+            Ok(())
+        }
+
+        #[test]
+        fn failing_hosts_get_reported_in_the_status_bar() -> R<()> {
+            let (process_watcher, _agents) = ProcessWatcher::fake_hosts_with_failing(
+                vec![("build-1", vec![Process::fake(1, 10.0, None)])],
+                &["build-2"],
+            )?;
+            let mut app = PorcApp::new(process_watcher, Settings::default())?;
+            app.tick()?;
+            assert_snapshot!(render_ui(app));
+            Ok(())
+        }
+
+        #[test]
+        fn fails_when_no_host_can_be_reached() {
+            let result = ProcessWatcher::fake_hosts_with_failing(vec![], &["build-1", "build-2"]);
+            assert!(result.is_err());
+        }
+    }
+
+    #[test]
+    fn signals_get_sent_through_the_process_watcher() -> R<()> {
+        let mut app = test_app(vec![Process::fake(1, 0.0, None)])?;
+        simulate_key_press(&mut app, KeyCode::Enter)?;
+        simulate_key_press(&mut app, KeyCode::Char('k'))?;
+        assert_eq!(
+            app.process_watcher.sent_signals(),
+            &[(1.into(), Signal::SIGKILL)]
+        );
+        Ok(())
+    }
+
+    mod heat_map {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        fn render_colors(settings: Settings) -> R<Buffer> {
+            render_processes_colors(
+                vec![Process::fake(1, 60.0, None), Process::fake(2, 150.0, None)],
+                Settings {
+                    columns: vec![Column::Cpu, Column::Pid],
+                    ..settings
+                },
+            )
+        }
+
+        fn render_processes_colors(processes: Vec<Process>, settings: Settings) -> R<Buffer> {
+            let mut app = test_app_with_settings(processes, settings)?;
+            let area = Rect::new(0, 0, 80, 10);
+            let mut buffer = Buffer::empty(area);
+            app.render(area, &mut buffer);
+            Ok(buffer)
+        }
+
+        fn cpu_cell_colors(buffer: &Buffer) -> Vec<Color> {
+            (2..4).map(|y| buffer[(3, y)].fg).collect()
+        }
+
+        #[test]
+        fn is_off_by_default() -> R<()> {
+            let buffer = render_colors(Settings::default())?;
+            assert_eq!(cpu_cell_colors(&buffer), vec![Color::Reset; 2]);
+            Ok(())
+        }
+
+        #[test]
+        fn colors_cpu_cells_relative_to_the_system_capacity() -> R<()> {
+            let buffer = render_colors(Settings {
+                heat_map: HeatMap::System,
+                ..Settings::default()
+            })?;
+            assert_eq!(cpu_cell_colors(&buffer), vec![Color::Green, Color::Yellow]);
+            Ok(())
+        }
+
+        #[test]
+        fn colors_cpu_cells_relative_to_the_view_maximum() -> R<()> {
+            let buffer = render_colors(Settings {
+                heat_map: HeatMap::View,
+                ..Settings::default()
+            })?;
+            assert_eq!(cpu_cell_colors(&buffer), vec![Color::Yellow, Color::Red]);
+            Ok(())
+        }
+
+        #[test]
+        fn tints_process_names() -> R<()> {
+            let buffer = render_colors(Settings {
+                heat_map: HeatMap::System,
+                tint_rows: true,
+                ..Settings::default()
+            })?;
+            assert_eq!(buffer[(19, 3)].symbol(), "t");
+            assert_eq!(buffer[(19, 3)].fg, Color::Yellow);
+            Ok(())
+        }
+
+        #[test]
+        fn uses_the_usage_of_processes_without_their_children() -> R<()> {
+            let buffer = render_processes_colors(
+                vec![
+                    Process::fake(1, 0.0, None),
+                    Process::fake(2, 150.0, Some(1)),
+                ],
+                Settings {
+                    columns: vec![Column::Cpu, Column::Pid],
+                    heat_map: HeatMap::View,
+                    ..Settings::default()
+                },
+            )?;
+            assert_eq!(cpu_cell_colors(&buffer), vec![Color::Reset, Color::Red]);
+            Ok(())
+        }
+
+        #[test]
+        fn tints_only_by_the_shown_columns() -> R<()> {
+            let buffer = render_processes_colors(
+                vec![Process::fake(1, 150.0, None)],
+                Settings {
+                    columns: vec![Column::Pid],
+                    heat_map: HeatMap::System,
+                    tint_rows: true,
+                    ..Settings::default()
+                },
+            )?;
+            assert_eq!(buffer[(13, 2)].symbol(), "o");
+            assert_eq!(buffer[(13, 2)].fg, Color::Reset);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn state_contains_the_sort_column_and_valid_patterns() -> R<()> {
+        let mut app = test_app(vec![Process::fake(1, 0.0, None)])?;
+        app.sorting = Sorting::new(Column::Cpu);
+        set_pattern(&mut app, "foo")?;
+        assert_eq!(
+            app.state(),
+            State {
+                sort: Some(Sorting::new(Column::Cpu)),
+                pattern: Some("foo".to_string()),
+            }
+        );
+        app.pattern.modify(|pattern| pattern.push('('));
+        assert_eq!(app.state().pattern, None);
+        Ok(())
+    }
+
+    #[test]
+    fn processes_get_sorted_by_pid() -> R<()> {
+        let app = test_app(vec![
+            Process::fake(1, 1.0, None),
+            Process::fake(2, 2.0, None),
+            Process::fake(3, 4.0, None),
+            Process::fake(4, 3.0, None),
+        ])?;
+        assert_snapshot!(render_ui(app));
+        Ok(())
+    }
+
+    #[test]
+    fn processes_can_be_sorted_by_cpu() -> R<()> {
+        let mut app = test_app(vec![
+            Process::fake(1, 1.0, None),
+            Process::fake(2, 2.0, None),
+            Process::fake(3, 4.0, None),
+            Process::fake(4, 3.0, None),
+        ])?;
+        simulate_key_press(&mut app, KeyCode::Tab)?;
+        assert_snapshot!(render_ui(app));
+        Ok(())
+    }
+
+    #[test]
+    fn sort_order_can_be_reversed() -> R<()> {
+        let mut app = test_app(vec![
+            Process::fake(1, 1.0, None),
+            Process::fake(2, 2.0, None),
+            Process::fake(3, 4.0, None),
+            Process::fake(4, 3.0, None),
+        ])?;
+        simulate_key_press(&mut app, KeyCode::Tab)?;
+        simulate_key_press(&mut app, KeyCode::Char('r'))?;
+        assert_snapshot!(render_ui(app));
+        Ok(())
+    }
+
+    fn sorted_pids(app: &PorcApp) -> Vec<u32> {
+        app.rows
+            .iter()
+            .filter_map(|row| row.node.id().pid())
+            .map(|pid| pid.as_u32())
+            .collect()
+    }
+
+    #[test]
+    fn processes_can_be_sorted_by_name() -> R<()> {
+        let app = test_app_with_settings(
+            (1..=4).map(|pid| Process::fake(pid, 0.0, None)).collect(),
+            Settings {
+                sort: Sorting::new(SortColumn::Name),
+                ..Settings::default()
+            },
+        )?;
+        // four, one, three, two
+        assert_eq!(sorted_pids(&app), vec![4, 1, 3, 2]);
+        Ok(())
+    }
+
+    #[test]
+    fn secondary_sort_keys_break_ties() -> R<()> {
+        let app = test_app_with_settings(
+            vec![
+                Process::fake(1, 1.0, None),
+                Process::fake(2, 2.0, None),
+                Process::fake(3, 1.0, None),
+                Process::fake(4, 2.0, None),
+            ],
+            Settings {
+                sort: "cpu:asc,pid:desc".parse()?,
+                ..Settings::default()
+            },
+        )?;
+        assert_eq!(sorted_pids(&app), vec![3, 1, 4, 2]);
+        Ok(())
+    }
+
+    #[test]
+    fn more_complicated_tree() -> R<()> {
+        let app = test_app(vec![
+            Process::fake(1, 1.0, None),
+            Process::fake(2, 2.0, Some(1)),
+            Process::fake(3, 3.0, Some(2)),
+            Process::fake(4, 4.0, Some(1)),
             Process::fake(5, 5.0, Some(4)),
             Process::fake(6, 5.0, Some(4)),
             Process::fake(7, 5.0, Some(6)),
@@ -408,7 +1820,7 @@ mod test {
             Process::fake(7, 5.0, Some(6)),
         ])?;
         set_pattern(&mut app, "four")?;
-        app.tick();
+        app.tick()?;
         assert_snapshot!(render_ui(app));
         Ok(())
     }
@@ -422,7 +1834,7 @@ mod test {
             Process::fake(4, 0.0, Some(1)),
         ])?;
         set_pattern(&mut app, "two|three")?;
-        app.tick();
+        app.tick()?;
         assert_snapshot!(render_ui(app));
         Ok(())
     }
@@ -435,11 +1847,167 @@ mod test {
             Process::fake(3, 0.0, None),
         ])?;
         set_pattern(&mut app, "2")?;
-        app.tick();
+        app.tick()?;
         assert_snapshot!(render_ui(app));
         Ok(())
     }
 
+    #[test]
+    fn filtering_by_namespace_pid() -> R<()> {
+        let mut app = test_app_with_settings(
+            vec![
+                Process::fake(1, 0.0, None),
+                Process::fake(2, 0.0, Some(1)).with_namespace_pid(7),
+                Process::fake(3, 0.0, Some(2)).with_namespace_pid(8),
+            ],
+            Settings {
+                columns: vec![Column::Pid, Column::NsPid],
+                ..Settings::default()
+            },
+        )?;
+        set_pattern(&mut app, "^8$")?;
+        app.tick()?;
+        assert_snapshot!(render_ui(app));
+        Ok(())
+    }
+
+    #[test]
+    fn rendering_does_not_panic_in_tiny_areas() -> R<()> {
+        for width in 0..=90 {
+            for height in 0..=12 {
+                let mut app = test_app(vec![
+                    Process::fake(1, 0.0, None),
+                    Process::fake(2, 0.0, Some(1)),
+                ])?;
+                set_pattern(&mut app, "one|two")?;
+                let area = Rect::new(3, 2, width, height);
+                app.render(area, &mut Buffer::empty(Rect::new(0, 0, 100, 20)));
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn shows_a_message_when_the_terminal_is_too_small() -> R<()> {
+        let app = test_app(vec![Process::fake(1, 0.0, None)])?;
+        assert_snapshot!(render_ui_with_area(app, Rect::new(0, 0, 30, 3)));
+        Ok(())
+    }
+
+    #[test]
+    fn wraps_the_status_bar_in_narrow_terminals() -> R<()> {
+        let mut app = test_app(vec![
+            Process::fake(1, 0.0, None),
+            Process::fake(2, 0.0, Some(1)),
+        ])?;
+        simulate_key_press(&mut app, KeyCode::Enter)?;
+        assert_snapshot!(render_ui_with_area(app, Rect::new(0, 0, 50, 8)));
+        Ok(())
+    }
+
+    #[test]
+    fn renders_the_status_bar_at_the_bottom_of_the_area() -> R<()> {
+        let mut app = test_app(vec![Process::fake(1, 0.0, None)])?;
+        let area = Rect::new(0, 3, 80, 6);
+        let mut buffer = Buffer::empty(Rect::new(0, 0, 80, 12));
+        app.render(area, &mut buffer);
+        assert_eq!(buffer[(0, 8)].symbol(), "C");
+        assert_eq!(buffer[(0, 11)].symbol(), " ");
+        Ok(())
+    }
+
+    #[test]
+    fn shows_a_help_overlay() -> R<()> {
+        let mut app = test_app(vec![Process::fake(1, 0.0, None)])?;
+        simulate_key_press(&mut app, KeyCode::Char('?'))?;
+        assert_snapshot!(render_ui_with_area(app, Rect::new(0, 0, 80, 24)));
+        Ok(())
+    }
+
+    #[test]
+    fn the_help_overlay_scrolls() -> R<()> {
+        let mut app = test_app(vec![Process::fake(1, 0.0, None)])?;
+        simulate_key_press(&mut app, KeyCode::Char('?'))?;
+        simulate_key_press(&mut app, KeyCode::End)?;
+        assert!(app.help_visible);
+        assert_snapshot!(render_ui_with_area(app, Rect::new(0, 0, 80, 24)));
+        Ok(())
+    }
+
+    #[test]
+    fn shows_a_debug_overlay_with_timings() -> R<()> {
+        let mut app = test_app(vec![Process::fake(1, 0.0, None)])?;
+        simulate_key_press(&mut app, KeyCode::F(12))?;
+        app.anomalies = 2;
+        app.timings = Timings {
+            refresh: Duration::from_micros(12_300),
+            build: Duration::from_micros(1_500),
+            filter: Duration::from_micros(800),
+            render: Duration::from_micros(2_000),
+        };
+        assert_snapshot!(render_ui(app));
+        Ok(())
+    }
+
+    #[test]
+    fn counts_anomalies_in_the_snapshot() -> R<()> {
+        let app = test_app(vec![
+            Process::fake(1, 0.0, None),
+            Process::fake(2, 0.0, Some(7)),
+        ])?;
+        assert_eq!(app.anomalies, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn the_debug_overlay_gets_toggled() -> R<()> {
+        let mut app = test_app(vec![Process::fake(1, 0.0, None)])?;
+        simulate_key_press(&mut app, KeyCode::F(12))?;
+        assert!(app.debug_overlay_visible);
+        simulate_key_press(&mut app, KeyCode::F(12))?;
+        assert!(!app.debug_overlay_visible);
+        Ok(())
+    }
+
+    #[test]
+    fn any_key_closes_the_help_overlay() -> R<()> {
+        let mut app = test_app(vec![
+            Process::fake(1, 0.0, None),
+            Process::fake(2, 0.0, None),
+        ])?;
+        simulate_key_press(&mut app, KeyCode::F(1))?;
+        assert!(app.help_visible);
+        simulate_key_press(&mut app, KeyCode::Down)?;
+        assert!(app.help_visible);
+        simulate_key_press(&mut app, KeyCode::Char('x'))?;
+        assert!(!app.help_visible);
+        assert_eq!(app.list_state.selected(), Some(0));
+        Ok(())
+    }
+
+    #[test]
+    fn question_marks_get_typed_into_the_pattern() -> R<()> {
+        let mut app = test_app(vec![])?;
+        simulate_key_press(&mut app, KeyCode::Char('/'))?;
+        simulate_key_press(&mut app, KeyCode::Char('a'))?;
+        simulate_key_press(&mut app, KeyCode::Char('?'))?;
+        assert_eq!(app.pattern.as_str(), "a?");
+        assert!(!app.help_visible);
+        simulate_key_press(&mut app, KeyCode::F(1))?;
+        assert!(app.help_visible);
+        Ok(())
+    }
+
+    #[test]
+    fn jumping_to_the_top_and_bottom() -> R<()> {
+        let mut app = test_app((1..=5).map(|pid| Process::fake(pid, 0.0, None)).collect())?;
+        simulate_key_press(&mut app, KeyCode::End)?;
+        assert_eq!(app.list_state.selected(), Some(4));
+        simulate_key_press(&mut app, KeyCode::Home)?;
+        assert_eq!(app.list_state.selected(), Some(0));
+        Ok(())
+    }
+
     #[test]
     fn typing_patterns() -> R<()> {
         let mut app = test_app(vec![
@@ -478,12 +2046,355 @@ mod test {
         ])?;
         assert_eq!(app.ui_mode, UiMode::Normal);
         simulate_key_press(&mut app, KeyCode::Enter)?;
-        assert_eq!(app.ui_mode, UiMode::ProcessSelected(1.into()));
+        assert_eq!(app.ui_mode, UiMode::ProcessSelected);
+        assert_eq!(app.marked, HashSet::from([1.into()]));
         simulate_key_press(&mut app, KeyCode::Esc)?;
         assert_eq!(app.ui_mode, UiMode::Normal);
+        assert_eq!(app.marked, HashSet::new());
         simulate_key_press(&mut app, KeyCode::Down)?;
         simulate_key_press(&mut app, KeyCode::Enter)?;
-        assert_eq!(app.ui_mode, UiMode::ProcessSelected(2.into()));
+        assert_eq!(app.marked, HashSet::from([2.into()]));
         Ok(())
     }
+
+    mod marking {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        fn test_app() -> R<PorcApp> {
+            super::test_app(vec![
+                Process::fake_named(1, "shell", 0.0, None),
+                Process::fake_named(2, "server", 0.0, Some(1)),
+                Process::fake_named(3, "server", 0.0, Some(1)),
+                Process::fake_named(4, "editor", 0.0, Some(1)),
+            ])
+        }
+
+        #[test]
+        fn space_toggles_the_mark_and_moves_down() -> R<()> {
+            let mut app = test_app()?;
+            simulate_key_press(&mut app, KeyCode::Down)?;
+            simulate_key_press(&mut app, KeyCode::Char(' '))?;
+            simulate_key_press(&mut app, KeyCode::Char(' '))?;
+            assert_eq!(app.marked, HashSet::from([2.into(), 3.into()]));
+            assert_eq!(app.list_state.selected(), Some(3));
+            assert_eq!(app.ui_mode, UiMode::ProcessSelected);
+            simulate_key_press(&mut app, KeyCode::Up)?;
+            simulate_key_press(&mut app, KeyCode::Char(' '))?;
+            assert_eq!(app.marked, HashSet::from([2.into()]));
+            simulate_key_press(&mut app, KeyCode::Up)?;
+            simulate_key_press(&mut app, KeyCode::Up)?;
+            simulate_key_press(&mut app, KeyCode::Char(' '))?;
+            assert_eq!(app.marked, HashSet::new());
+            assert_eq!(app.ui_mode, UiMode::Normal);
+            Ok(())
+        }
+
+        #[test]
+        fn marks_all_processes_matching_the_pattern() -> R<()> {
+            let mut app = test_app()?;
+            simulate_key_press(&mut app, KeyCode::Char('a'))?;
+            assert_eq!(app.marked, HashSet::new());
+            set_pattern(&mut app, "server")?;
+            simulate_key_press(&mut app, KeyCode::Char('a'))?;
+            assert_eq!(app.marked, HashSet::from([2.into(), 3.into()]));
+            assert_snapshot!(render_ui(app));
+            Ok(())
+        }
+
+        #[test]
+        fn marks_survive_filtering() -> R<()> {
+            let mut app = test_app()?;
+            set_pattern(&mut app, "server")?;
+            simulate_key_press(&mut app, KeyCode::Char('a'))?;
+            set_pattern(&mut app, "editor")?;
+            assert_eq!(app.marked, HashSet::from([2.into(), 3.into()]));
+            assert_eq!(app.ui_mode, UiMode::ProcessSelected);
+            simulate_key_press(&mut app, KeyCode::End)?;
+            simulate_key_press(&mut app, KeyCode::Char(' '))?;
+            simulate_key_press(&mut app, KeyCode::Char('k'))?;
+            assert_eq!(
+                app.process_watcher.sent_signals(),
+                &[
+                    (2.into(), Signal::SIGKILL),
+                    (3.into(), Signal::SIGKILL),
+                    (4.into(), Signal::SIGKILL)
+                ]
+            );
+            Ok(())
+        }
+
+        #[test]
+        fn signals_go_to_all_marked_processes() -> R<()> {
+            let mut app = test_app()?;
+            set_pattern(&mut app, "server")?;
+            simulate_key_press(&mut app, KeyCode::Char('a'))?;
+            simulate_key_press(&mut app, KeyCode::Char('t'))?;
+            assert_eq!(
+                app.process_watcher.sent_signals(),
+                &[(2.into(), Signal::SIGTERM), (3.into(), Signal::SIGTERM)]
+            );
+            Ok(())
+        }
+
+        #[test]
+        fn stopping_and_continuing_marked_processes() -> R<()> {
+            let mut app = super::test_app(vec![
+                Process::fake_named(1, "shell", 0.0, None),
+                Process::fake_named(2, "build", 0.0, Some(1)),
+                Process::fake_named(3, "indexer", 0.0, Some(1)).with_stopped(),
+            ])?;
+            simulate_key_press(&mut app, KeyCode::Down)?;
+            simulate_key_press(&mut app, KeyCode::Char(' '))?;
+            simulate_key_press(&mut app, KeyCode::Char(' '))?;
+            simulate_key_press(&mut app, KeyCode::Char('s'))?;
+            assert_eq!(
+                app.process_watcher.sent_signals(),
+                &[(2.into(), Signal::SIGSTOP), (3.into(), Signal::SIGCONT)]
+            );
+            assert_snapshot!(render_ui(app));
+            Ok(())
+        }
+
+        #[test]
+        fn exporting_prints_the_marked_processes() -> R<()> {
+            let mut app = test_app()?;
+            simulate_key_press(&mut app, KeyCode::Char('x'))?;
+            assert_eq!(app.exported(), &[] as &[String]);
+            simulate_key_press(&mut app, KeyCode::Down)?;
+            simulate_key_press(&mut app, KeyCode::Char(' '))?;
+            simulate_key_press(&mut app, KeyCode::Char(' '))?;
+            assert!(matches!(
+                simulate_key_press(&mut app, KeyCode::Char('x'))?,
+                UpdateResult::Exit
+            ));
+            assert_eq!(app.exported(), &["2\tserver", "3\tserver"]);
+            Ok(())
+        }
+
+        fn type_keys(app: &mut PorcApp, keys: &str) -> R<()> {
+            for key in keys.chars() {
+                simulate_key_press(app, KeyCode::Char(key))?;
+            }
+            Ok(())
+        }
+
+        #[test]
+        fn scheduling_changes_go_to_all_marked_processes() -> R<()> {
+            let mut app = test_app()?;
+            set_pattern(&mut app, "server")?;
+            simulate_key_press(&mut app, KeyCode::Char('a'))?;
+            type_keys(&mut app, "n10")?;
+            assert_eq!(app.ui_mode, UiMode::Prompting(Prompt::Nice));
+            simulate_key_press(&mut app, KeyCode::Enter)?;
+            assert_eq!(app.ui_mode, UiMode::ProcessSelected);
+            type_keys(&mut app, "p0-1")?;
+            simulate_key_press(&mut app, KeyCode::Enter)?;
+            assert_eq!(
+                app.process_watcher.schedulings(),
+                &[
+                    (2.into(), Scheduling::Nice(10)),
+                    (3.into(), Scheduling::Nice(10)),
+                    (2.into(), Scheduling::Affinity(vec![0, 1])),
+                    (3.into(), Scheduling::Affinity(vec![0, 1])),
+                ]
+            );
+            Ok(())
+        }
+
+        #[test]
+        fn invalid_values_are_shown_in_the_status_bar() -> R<()> {
+            let mut app = test_app()?;
+            simulate_key_press(&mut app, KeyCode::Char(' '))?;
+            type_keys(&mut app, "ifast")?;
+            simulate_key_press(&mut app, KeyCode::Enter)?;
+            assert_eq!(app.ui_mode, UiMode::Prompting(Prompt::IoPriority));
+            assert_eq!(app.process_watcher.schedulings(), &[]);
+            assert_snapshot!(render_ui(app));
+            Ok(())
+        }
+
+        #[test]
+        fn pattern_and_sort_keys_get_typed_into_prompts() -> R<()> {
+            let mut app = test_app()?;
+            simulate_key_press(&mut app, KeyCode::Char(' '))?;
+            type_keys(&mut app, "p/")?;
+            assert_eq!(app.ui_mode, UiMode::Prompting(Prompt::Affinity));
+            assert_eq!(app.input, "/");
+            let sorting = app.sorting.clone();
+            simulate_key_press(&mut app, KeyCode::Tab)?;
+            assert_eq!(app.ui_mode, UiMode::Prompting(Prompt::Affinity));
+            assert_eq!(app.sorting, sorting);
+            Ok(())
+        }
+
+        #[test]
+        fn prompts_can_be_cancelled() -> R<()> {
+            let mut app = test_app()?;
+            simulate_key_press(&mut app, KeyCode::Char(' '))?;
+            type_keys(&mut app, "n5")?;
+            simulate_key_press(&mut app, KeyCode::Backspace)?;
+            assert_eq!(app.input, "");
+            simulate_key_press(&mut app, KeyCode::Esc)?;
+            assert_eq!(app.ui_mode, UiMode::ProcessSelected);
+            assert_eq!(app.process_watcher.schedulings(), &[]);
+            Ok(())
+        }
+
+        #[test]
+        fn marks_of_exited_processes_get_dropped() -> R<()> {
+            let mut app = test_app()?;
+            simulate_key_press(&mut app, KeyCode::Down)?;
+            simulate_key_press(&mut app, KeyCode::Char(' '))?;
+            app.process_watcher.set_processes(vec![
+                Process::fake_named(1, "shell", 0.0, None),
+                Process::fake_named(3, "server", 0.0, Some(1)),
+            ]);
+            app.tick()?;
+            assert_eq!(app.marked, HashSet::new());
+            assert_eq!(app.ui_mode, UiMode::Normal);
+            Ok(())
+        }
+    }
+
+    mod mouse {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn clicking_a_row_moves_the_cursor() -> R<()> {
+            let mut app = test_app(vec![
+                Process::fake(1, 0.0, None),
+                Process::fake(2, 0.0, Some(1)),
+                Process::fake(3, 0.0, None),
+            ])?;
+            simulate_click(&mut app, 30, 4)?;
+            assert_eq!(app.list_state.selected(), Some(2));
+            assert_eq!(app.ui_mode, UiMode::Normal);
+            Ok(())
+        }
+
+        #[test]
+        fn clicking_below_the_last_row_does_nothing() -> R<()> {
+            let mut app = test_app(vec![
+                Process::fake(1, 0.0, None),
+                Process::fake(2, 0.0, None),
+            ])?;
+            simulate_click(&mut app, 30, 6)?;
+            assert_eq!(app.list_state.selected(), Some(0));
+            Ok(())
+        }
+
+        #[test]
+        fn double_clicking_a_row_selects_the_process() -> R<()> {
+            let mut app = test_app(vec![
+                Process::fake(1, 0.0, None),
+                Process::fake(2, 0.0, Some(1)),
+            ])?;
+            simulate_click(&mut app, 30, 3)?;
+            simulate_click(&mut app, 30, 3)?;
+            assert_eq!(app.marked, HashSet::from([2.into()]));
+            Ok(())
+        }
+
+        #[test]
+        fn clicking_two_different_rows_does_not_select() -> R<()> {
+            let mut app = test_app(vec![
+                Process::fake(1, 0.0, None),
+                Process::fake(2, 0.0, Some(1)),
+            ])?;
+            simulate_click(&mut app, 30, 2)?;
+            simulate_click(&mut app, 30, 3)?;
+            assert_eq!(app.ui_mode, UiMode::Normal);
+            assert_eq!(app.list_state.selected(), Some(1));
+            Ok(())
+        }
+
+        #[test]
+        fn clicking_header_columns_changes_the_sort_column() -> R<()> {
+            let mut app = test_app(vec![Process::fake(1, 0.0, None)])?;
+            simulate_click(&mut app, 12, 0)?;
+            assert_eq!(app.sorting, "cpu".parse()?);
+            simulate_click(&mut app, 22, 0)?;
+            assert_eq!(app.sorting, "ram".parse()?);
+            simulate_click(&mut app, 6, 0)?;
+            assert_eq!(app.sorting, "pid".parse()?);
+            simulate_click(&mut app, 40, 0)?;
+            assert_eq!(app.sorting, "name".parse()?);
+            Ok(())
+        }
+
+        #[test]
+        fn clicks_do_nothing_when_the_terminal_is_too_small() -> R<()> {
+            let mut app = test_app(vec![
+                Process::fake(1, 0.0, None),
+                Process::fake(2, 0.0, None),
+            ])?;
+            let sorting = app.sorting.clone();
+            let area = Rect::new(0, 0, 80, 10);
+            app.render(area, &mut Buffer::empty(area));
+            let tiny = Rect::new(0, 0, 30, 3);
+            let click = MouseEventKind::Down(MouseButton::Left);
+            simulate_mouse_in(&mut app, tiny, click, 12, 0)?;
+            simulate_mouse_in(&mut app, tiny, click, 20, 2)?;
+            assert_eq!(app.sorting, sorting);
+            assert_eq!(app.list_state.selected(), Some(0));
+            Ok(())
+        }
+
+        #[test]
+        fn clicking_the_sorted_column_again_reverses_the_order() -> R<()> {
+            let mut app = test_app(vec![Process::fake(1, 0.0, None)])?;
+            simulate_click(&mut app, 12, 0)?;
+            simulate_click(&mut app, 12, 0)?;
+            assert_eq!(app.sorting, "cpu:asc".parse()?);
+            Ok(())
+        }
+
+        fn scroll_test_app() -> R<PorcApp> {
+            test_app(
+                (1..=20)
+                    .map(|pid| Process::fake_named(pid, &format!("worker-{pid}"), 0.0, None))
+                    .collect(),
+            )
+        }
+
+        #[test]
+        fn scrolling_moves_the_viewport() -> R<()> {
+            let mut app = scroll_test_app()?;
+            for _ in 0..4 {
+                simulate_key_press(&mut app, KeyCode::Down)?;
+            }
+            simulate_mouse(&mut app, MouseEventKind::ScrollDown, 30, 4)?;
+            assert_eq!(app.list_state.offset(), 3);
+            assert_eq!(app.list_state.selected(), Some(4));
+            simulate_mouse(&mut app, MouseEventKind::ScrollUp, 30, 4)?;
+            assert_eq!(app.list_state.offset(), 0);
+            assert_eq!(app.list_state.selected(), Some(4));
+            Ok(())
+        }
+
+        #[test]
+        fn scrolling_keeps_the_cursor_in_view() -> R<()> {
+            let mut app = scroll_test_app()?;
+            simulate_mouse(&mut app, MouseEventKind::ScrollDown, 30, 4)?;
+            assert_eq!(app.list_state.selected(), Some(3));
+            simulate_key_press(&mut app, KeyCode::End)?;
+            simulate_mouse(&mut app, MouseEventKind::ScrollUp, 30, 4)?;
+            let last_visible = app.list_state.offset() + usize::from(app.list_rect.height) - 1;
+            assert_eq!(app.list_state.selected(), Some(last_visible));
+            Ok(())
+        }
+
+        #[test]
+        fn scrolling_stops_at_the_end_of_the_list() -> R<()> {
+            let mut app = scroll_test_app()?;
+            for _ in 0..10 {
+                simulate_mouse(&mut app, MouseEventKind::ScrollDown, 30, 4)?;
+            }
+            let height = usize::from(app.list_rect.height);
+            assert_eq!(app.list_state.offset(), 20 - height);
+            Ok(())
+        }
+    }
 }
@@ -1,33 +1,97 @@
+use crate::agent::{Address, Connection};
+use crate::cgroup::{read_cgroup, units, Container};
+use crate::grouping::GroupBy;
+use crate::namespace::read_namespace_pid;
+use crate::regex::Regex;
+use crate::scheduling::Scheduling;
 pub(crate) use crate::tree::Forest;
-use crate::tree::Node;
+use crate::tree::{Anomaly, Node};
+use crate::R;
+use clap::ValueEnum;
+use nix::sys::signal::Signal;
 use num_format::Locale;
 use num_format::ToFormattedString;
 use ratatui::buffer::Buffer;
 use ratatui::layout::Rect;
-use ratatui::prelude::Stylize;
+use ratatui::style::Color;
 use ratatui::style::Modifier;
 use ratatui::style::Style;
 use ratatui::text::Line;
 use ratatui::text::Span;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
 use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::path::Path;
+use std::str::FromStr;
 use sysinfo::Pid;
 use sysinfo::ProcessRefreshKind;
 use sysinfo::ThreadKind;
 use sysinfo::UpdateKind;
 
 #[derive(Debug, Clone)]
-pub(crate) struct Process {
-    pid: Pid,
+pub struct Process {
+    id: ProcessId,
     pub(crate) name: String,
     arguments: Vec<String>,
-    parent: Option<Pid>,
+    parent: Option<ProcessId>,
     cpu: f32,
     ram: u64,
+    /// `cpu` and `ram` without the descendants, for the heat map
+    own_cpu: f32,
+    own_ram: u64,
+    pub(crate) container: Option<Container>,
+    pub(crate) cgroup: Option<String>,
+    pub(crate) user: Option<String>,
+    /// The pid inside the process's own pid namespace, e.g. in a container.
+    /// Only set for processes in a different namespace than porc.
+    pub(crate) namespace_pid: Option<u32>,
+    /// The number of identical siblings collapsed into this row.
+    pub(crate) count: usize,
+    /// the processes collapsed into this row, empty for other rows
+    merged: Vec<ProcessId>,
+    /// The machine the process runs on, only set when watching several hosts.
+    pub(crate) host: Option<String>,
+    /// Stopped by a signal like `SIGSTOP`, until it gets a `SIGCONT`.
+    pub(crate) stopped: bool,
+}
+
+/// Nodes in the process forest are either processes or synthetic group nodes,
+/// e.g. one per container when grouping by container.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[non_exhaustive]
+pub enum ProcessId {
+    Pid(Pid),
+    /// a process on the host with the given index, see `ProcessWatcher::connect`
+    Remote(usize, Pid),
+    Group(u64),
+}
+
+impl ProcessId {
+    pub fn pid(self) -> Option<Pid> {
+        match self {
+            ProcessId::Pid(pid) | ProcessId::Remote(_, pid) => Some(pid),
+            ProcessId::Group(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for ProcessId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProcessId::Pid(pid) | ProcessId::Remote(_, pid) => f.pad(&pid.to_string()),
+            ProcessId::Group(_) => f.pad(""),
+        }
+    }
 }
 
 impl fmt::Display for Process {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.count > 1 {
+            return write!(f, "{} ×{}", self.name, self.count);
+        }
         match self.arguments.first() {
             Some(executable) => match Path::new(&executable).file_name() {
                 Some(file_name) => write!(f, "{}", file_name.to_string_lossy())?,
@@ -43,13 +107,13 @@ impl fmt::Display for Process {
 }
 
 impl Node for Process {
-    type Id = Pid;
+    type Id = ProcessId;
 
-    fn id(&self) -> Pid {
-        self.pid
+    fn id(&self) -> ProcessId {
+        self.id
     }
 
-    fn parent(&self) -> Option<Pid> {
+    fn parent(&self) -> Option<ProcessId> {
         self.parent
     }
 
@@ -60,9 +124,41 @@ impl Node for Process {
 }
 
 impl Process {
-    fn from_sysinfo_process(process: &sysinfo::Process) -> Self {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn arguments(&self) -> &[String] {
+        &self.arguments
+    }
+
+    /// In percent of one core, including all descendants when taken from a
+    /// forest.
+    pub fn cpu(&self) -> f32 {
+        self.cpu
+    }
+
+    /// In bytes, including all descendants when taken from a forest.
+    pub fn ram(&self) -> u64 {
+        self.ram
+    }
+
+    pub fn user(&self) -> Option<&str> {
+        self.user.as_deref()
+    }
+
+    pub fn cgroup(&self) -> Option<&str> {
+        self.cgroup.as_deref()
+    }
+
+    fn from_sysinfo_process(
+        process: &sysinfo::Process,
+        users: &sysinfo::Users,
+        procfs: Option<&ProcfsInfo>,
+    ) -> Self {
+        let cgroup = procfs.and_then(|procfs| procfs.cgroup.clone());
         Process {
-            pid: process.pid(),
+            id: ProcessId::Pid(process.pid()),
             name: match process.exe() {
                 Some(exe) => match exe.file_name() {
                     Some(file_name) => file_name.to_string_lossy().to_string(),
@@ -71,181 +167,1091 @@ impl Process {
                 None => process.name().to_string(),
             },
             arguments: process.cmd().to_vec(),
-            parent: process.parent(),
+            parent: process.parent().map(ProcessId::Pid),
             cpu: process.cpu_usage(),
             ram: process.memory(),
+            own_cpu: process.cpu_usage(),
+            own_ram: process.memory(),
+            container: cgroup.as_deref().and_then(Container::from_cgroup),
+            cgroup,
+            user: process
+                .user_id()
+                .map(|uid| match users.get_user_by_id(uid) {
+                    Some(user) => user.name().to_string(),
+                    None => uid.to_string(),
+                }),
+            namespace_pid: procfs.and_then(|procfs| procfs.namespace_pid),
+            count: 1,
+            merged: Vec::new(),
+            host: None,
+            stopped: process.status() == sysinfo::ProcessStatus::Stop,
         }
     }
 
-    pub(crate) fn compare(&self, other: &Process, sort_by: SortBy) -> std::cmp::Ordering {
-        let ordering = match sort_by {
-            SortBy::Pid => self.id().partial_cmp(&other.id()),
-            SortBy::Cpu => other.cpu.partial_cmp(&self.cpu),
-            SortBy::Ram => other.ram.partial_cmp(&self.ram),
-        };
-        match ordering {
-            Some(std::cmp::Ordering::Equal) => self.pid.cmp(&other.pid),
-            Some(ordering) => ordering,
-            None => self.pid.cmp(&other.pid),
+    /// Whether the name, the pid or the in-namespace pid match the pattern.
+    pub(crate) fn matches(&self, pattern: &Regex) -> bool {
+        pattern.is_match(&self.name)
+            || pattern.is_match(&self.id.to_string())
+            || self
+                .namespace_pid
+                .is_some_and(|pid| pattern.is_match(&pid.to_string()))
+    }
+
+    pub(crate) fn compare(&self, other: &Process, sorting: &Sorting) -> Ordering {
+        sorting
+            .0
+            .iter()
+            .map(|key| {
+                let ordering = self.compare_column(other, key.column);
+                match key.direction {
+                    Direction::Ascending => ordering,
+                    Direction::Descending => ordering.reverse(),
+                }
+            })
+            .find(|ordering| *ordering != Ordering::Equal)
+            .unwrap_or_else(|| self.id.cmp(&other.id))
+    }
+
+    fn compare_column(&self, other: &Process, column: SortColumn) -> Ordering {
+        match column {
+            // group ids are hashes, so groups get sorted by name instead
+            SortColumn::Column(Column::Pid) => match (self.id, other.id) {
+                (ProcessId::Group(_), ProcessId::Group(_)) => self.name.cmp(&other.name),
+                (id, other_id) => id.cmp(&other_id),
+            },
+            SortColumn::Column(Column::NsPid) => self.namespace_pid.cmp(&other.namespace_pid),
+            SortColumn::Name => self
+                .name
+                .chars()
+                .flat_map(char::to_lowercase)
+                .cmp(other.name.chars().flat_map(char::to_lowercase)),
+            SortColumn::Column(Column::Cpu) => {
+                self.cpu.partial_cmp(&other.cpu).unwrap_or(Ordering::Equal)
+            }
+            SortColumn::Column(Column::Ram) => self.ram.cmp(&other.ram),
+            SortColumn::Column(Column::Container) => self.container.cmp(&other.container),
+            SortColumn::Column(Column::Unit) => self.unit().cmp(&other.unit()),
+            SortColumn::Column(Column::User) => self.user.cmp(&other.user),
         }
     }
 
-    pub(crate) fn render_header(area: Rect, sort_by: SortBy, buffer: &mut Buffer) -> u16 {
+    pub(crate) fn render_header(
+        area: Rect,
+        columns: &[Column],
+        sorting: &Sorting,
+        lines: Color,
+        buffer: &mut Buffer,
+    ) -> u16 {
+        let primary = sorting.primary();
+        let label = |column: SortColumn| {
+            if column == primary.column {
+                Span::styled(
+                    format!("{}{}", column.header_label(), primary.direction.arrow()),
+                    Style::new().add_modifier(Modifier::REVERSED),
+                )
+            } else {
+                Span::raw(column.header_label())
+            }
+        };
         let table_header = {
             let mut line = Line::default();
-            for column in SortBy::all() {
-                let leading_spaces = match column {
-                    SortBy::Pid => 5,
-                    SortBy::Cpu => 3,
-                    SortBy::Ram => 7,
-                };
-                line.push_span(" ".repeat(leading_spaces));
-                line.push_span(Span::styled(
-                    format!("{:?}", column).to_lowercase(),
-                    if column == sort_by {
-                        Style::new().add_modifier(Modifier::REVERSED)
-                    } else {
-                        Style::new()
-                    },
-                ));
+            for (i, column) in columns.iter().enumerate() {
+                let label = label(SortColumn::Column(*column));
+                line.push_span(" ".repeat(header_leading_spaces(i, *column, label.width()).into()));
+                line.push_span(label);
             }
             line.push_span(" ");
             line
         };
         buffer.set_line(area.x, area.y, &table_header, area.width);
-        if let Ok(table_header_length) = table_header.width().try_into() {
-            if let Some(cell) = buffer.cell_mut((table_header_length, area.y)) {
+        if let Ok(table_header_length) = u16::try_from(table_header.width()) {
+            let separator_x = area.x + table_header_length;
+            if let Some(cell) = buffer.cell_mut((separator_x, area.y)) {
                 cell.set_symbol("┃");
-                cell.set_style(Style::new().dark_gray());
-            }
-            buffer.set_string(
-                area.x + table_header_length + 2,
-                area.y,
-                "executable",
-                Style::new(),
-            );
-            for x in (area.x)..(area.width) {
+                cell.set_style(Style::new().fg(lines));
+            }
+            let executable_x = separator_x + 2;
+            if executable_x < area.right() {
+                buffer.set_span(
+                    executable_x,
+                    area.y,
+                    &label(SortColumn::Name),
+                    area.right() - executable_x,
+                );
+            }
+            for x in area.left()..area.right() {
                 if let Some(cell) = buffer.cell_mut((x, area.y + 1)) {
-                    cell.set_symbol(if x == table_header_length {
-                        "╋"
-                    } else {
-                        "━"
-                    });
-                    cell.set_style(Style::new().dark_gray());
+                    cell.set_symbol(if x == separator_x { "╋" } else { "━" });
+                    cell.set_style(Style::new().fg(lines));
                 }
             }
         }
         2
     }
 
-    pub(crate) fn table_data(&self) -> String {
-        format!(
-            "{:>8} {:>4.0}% {:>7}MB",
-            self.pid.as_u32(),
-            self.cpu,
-            (self.ram / 2_u64.pow(20)).to_formatted_string(&Locale::en)
-        )
+    pub(crate) fn header_column_at(area: Rect, columns: &[Column], x: u16) -> Option<SortColumn> {
+        let mut start = area.x;
+        for (i, column) in columns.iter().enumerate() {
+            let end = start
+                + header_leading_spaces(i, *column, column.header_label().len())
+                + column.header_label().len() as u16;
+            if (start..end).contains(&x) {
+                return Some(SortColumn::Column(*column));
+            }
+            start = end;
+        }
+        if x > start + 1 {
+            Some(SortColumn::Name)
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn table_cell(&self, column: Column) -> String {
+        match column {
+            Column::Pid => format!("{:>8}", self.id),
+            Column::NsPid => text_cell(
+                column,
+                &self
+                    .namespace_pid
+                    .map(|pid| pid.to_string())
+                    .unwrap_or_default(),
+            ),
+            Column::Cpu => format!("{:>4.0}%", self.cpu),
+            Column::Ram => format!(
+                "{:>7}MB",
+                (self.ram / 2_u64.pow(20)).to_formatted_string(&Locale::en)
+            ),
+            Column::Container => text_cell(
+                column,
+                &self
+                    .container
+                    .as_ref()
+                    .map(|container| container.to_string())
+                    .unwrap_or_default(),
+            ),
+            Column::Unit => text_cell(column, self.unit().unwrap_or_default()),
+            Column::User => text_cell(column, self.user.as_deref().unwrap_or_default()),
+        }
+    }
+
+    /// The innermost systemd unit, e.g. `nginx.service`.
+    pub(crate) fn unit(&self) -> Option<&str> {
+        units(self.cgroup.as_deref()?).last().copied()
+    }
+
+    /// A node for a group of processes, e.g. all processes in a container.
+    /// Groups can be nested, so they're identified by their path.
+    pub(crate) fn group(path: &[String], parent: Option<ProcessId>) -> Process {
+        Process {
+            id: Process::group_id(path),
+            name: path.last().cloned().unwrap_or_default(),
+            arguments: Vec::new(),
+            parent,
+            cpu: 0.0,
+            ram: 0,
+            own_cpu: 0.0,
+            own_ram: 0,
+            container: None,
+            cgroup: None,
+            user: None,
+            namespace_pid: None,
+            count: 1,
+            merged: Vec::new(),
+            host: None,
+            stopped: false,
+        }
+    }
+
+    pub(crate) fn group_id(path: &[String]) -> ProcessId {
+        let mut hasher = DefaultHasher::new();
+        path.hash(&mut hasher);
+        ProcessId::Group(hasher.finish())
+    }
+
+    /// The id of the row that this process gets collapsed into, together
+    /// with its siblings of the same name. Group nodes don't get collapsed.
+    pub(crate) fn collapsed_id(&self, parent: Option<ProcessId>) -> Option<ProcessId> {
+        self.id.pid()?;
+        let mut hasher = DefaultHasher::new();
+        parent.hash(&mut hasher);
+        self.name.hash(&mut hasher);
+        Some(ProcessId::Group(hasher.finish()))
+    }
+
+    /// Merges identical siblings into one row, with their usage summed up.
+    pub(crate) fn collapse(id: ProcessId, processes: Vec<Process>) -> Process {
+        let mut processes = processes.into_iter();
+        let mut result = match processes.next() {
+            Some(first) => first,
+            None => Process::group(&[], None),
+        };
+        result.merged = result.ids();
+        result.id = id;
+        result.arguments = Vec::new();
+        for process in processes {
+            result.merged.extend(process.ids());
+            result.accumulate_from(&process);
+            result.own_cpu += process.own_cpu;
+            result.own_ram += process.own_ram;
+            result.count += process.count;
+            result.stopped &= process.stopped;
+        }
+        result
+    }
+
+    /// The processes this row stands for, i.e. the ones collapsed into it or
+    /// the process itself.
+    pub(crate) fn ids(&self) -> Vec<ProcessId> {
+        if self.merged.is_empty() {
+            vec![self.id]
+        } else {
+            self.merged.clone()
+        }
+    }
+
+    pub(crate) fn set_parent(&mut self, parent: Option<ProcessId>) {
+        self.parent = parent;
+    }
+
+    /// The fraction of the given capacity that the process itself uses,
+    /// without its descendants, for resource columns.
+    pub(crate) fn load(&self, column: Column, capacity: Capacity) -> Option<f32> {
+        match column {
+            Column::Pid | Column::NsPid | Column::Container | Column::Unit | Column::User => None,
+            Column::Cpu if capacity.cpu > 0.0 => Some(self.own_cpu / capacity.cpu),
+            Column::Ram if capacity.ram > 0 => Some(self.own_ram as f32 / capacity.ram as f32),
+            Column::Cpu | Column::Ram => Some(0.0),
+        }
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub(crate) enum SortBy {
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Capacity {
+    /// in percent, i.e. 100 per core
+    pub cpu: f32,
+    /// in bytes
+    pub ram: u64,
+}
+
+impl Capacity {
+    /// The maximum usage of all given processes, without their descendants.
+    pub(crate) fn max_of<'a>(processes: impl Iterator<Item = &'a Process>) -> Capacity {
+        processes.fold(Capacity { cpu: 0.0, ram: 0 }, |capacity, process| {
+            Capacity {
+                cpu: capacity.cpu.max(process.own_cpu),
+                ram: capacity.ram.max(process.own_ram),
+            }
+        })
+    }
+}
+
+fn text_cell(column: Column, text: &str) -> String {
+    let width = column.width().into();
+    format!("{:>width$.width$}", text, width = width)
+}
+
+fn header_leading_spaces(index: usize, column: Column, label_width: usize) -> u16 {
+    let separator = if index == 0 { 0 } else { 1 };
+    separator + column.width().saturating_sub(label_width as u16)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum Column {
     Pid,
+    /// the pid inside the process's pid namespace
+    NsPid,
     Cpu,
     Ram,
+    Container,
+    Unit,
+    User,
 }
 
 #[allow(clippy::derivable_impls)]
-impl Default for SortBy {
-    fn default() -> SortBy {
-        SortBy::Pid
+impl Default for Column {
+    fn default() -> Column {
+        Column::Pid
+    }
+}
+
+impl Column {
+    fn width(self) -> u16 {
+        match self {
+            Column::Pid | Column::NsPid => 8,
+            Column::Cpu => 5,
+            Column::Ram => 9,
+            Column::Container => 19,
+            Column::Unit => 24,
+            Column::User => 10,
+        }
+    }
+
+    fn header_label(self) -> String {
+        match self {
+            Column::NsPid => "ns-pid".to_string(),
+            Column::Pid
+            | Column::Cpu
+            | Column::Ram
+            | Column::Container
+            | Column::Unit
+            | Column::User => format!("{:?}", self).to_lowercase(),
+        }
+    }
+
+    /// The name used on the command line and in the config file.
+    fn name(self) -> String {
+        self.to_possible_value()
+            .map(|value| value.get_name().to_string())
+            .unwrap_or_default()
+    }
+
+    /// The columns shown when none are configured.
+    pub(crate) fn default_columns() -> Vec<Column> {
+        vec![Column::Pid, Column::Cpu, Column::Ram]
+    }
+}
+
+/// What processes can be sorted by: the shown columns and the executable
+/// name, which is always shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SortColumn {
+    Column(Column),
+    Name,
+}
+
+impl SortColumn {
+    fn header_label(self) -> String {
+        match self {
+            SortColumn::Column(column) => column.header_label(),
+            SortColumn::Name => "executable".to_string(),
+        }
+    }
+
+    fn default_direction(self) -> Direction {
+        match self {
+            SortColumn::Column(Column::Cpu | Column::Ram) => Direction::Descending,
+            SortColumn::Column(
+                Column::Pid | Column::NsPid | Column::Container | Column::Unit | Column::User,
+            )
+            | SortColumn::Name => Direction::Ascending,
+        }
+    }
+}
+
+impl From<Column> for SortColumn {
+    fn from(column: Column) -> SortColumn {
+        SortColumn::Column(column)
+    }
+}
+
+impl FromStr for SortColumn {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<SortColumn, String> {
+        match s {
+            "name" => Ok(SortColumn::Name),
+            s => Column::from_str(s, true)
+                .map(SortColumn::Column)
+                .map_err(|_| format!("invalid sort column: {}", s)),
+        }
     }
 }
 
-impl SortBy {
-    pub(crate) fn next(self) -> SortBy {
+impl fmt::Display for SortColumn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            SortBy::Pid => SortBy::Cpu,
-            SortBy::Cpu => SortBy::Ram,
-            SortBy::Ram => SortBy::Pid,
+            SortColumn::Column(column) => write!(f, "{}", column.name()),
+            SortColumn::Name => write!(f, "name"),
         }
     }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Direction {
+    Ascending,
+    Descending,
+}
 
-    fn all() -> impl Iterator<Item = SortBy> {
-        vec![SortBy::Pid, SortBy::Cpu, SortBy::Ram].into_iter()
+impl Direction {
+    fn reversed(self) -> Direction {
+        match self {
+            Direction::Ascending => Direction::Descending,
+            Direction::Descending => Direction::Ascending,
+        }
+    }
+
+    fn arrow(self) -> &'static str {
+        match self {
+            Direction::Ascending => "▲",
+            Direction::Descending => "▼",
+        }
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct SortKey {
+    pub(crate) column: SortColumn,
+    pub(crate) direction: Direction,
+}
+
+impl SortKey {
+    fn new(column: SortColumn) -> SortKey {
+        SortKey {
+            column,
+            direction: column.default_direction(),
+        }
+    }
+}
+
+/// Sort keys in order of priority, written like `cpu` or `ram:asc,name`.
+/// Ties between all keys are broken by pid.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub(crate) struct Sorting(Vec<SortKey>);
+
+impl Default for Sorting {
+    fn default() -> Sorting {
+        Sorting::new(Column::default())
+    }
+}
+
+impl Sorting {
+    pub(crate) fn new(column: impl Into<SortColumn>) -> Sorting {
+        Sorting(vec![SortKey::new(column.into())])
+    }
+
+    pub(crate) fn primary(&self) -> SortKey {
+        self.0[0]
+    }
+
+    /// Replaces the primary sort key, keeping the secondary keys.
+    fn set_primary(&mut self, key: SortKey) {
+        let mut keys = vec![key];
+        keys.extend(
+            self.0
+                .iter()
+                .skip(1)
+                .filter(|secondary| secondary.column != key.column),
+        );
+        self.0 = keys;
+    }
+
+    /// Sorts by the given column, or reverses the direction if already
+    /// sorting by it.
+    pub(crate) fn select(&mut self, column: SortColumn) {
+        let primary = self.primary();
+        if primary.column == column {
+            self.reverse();
+        } else {
+            self.set_primary(SortKey::new(column));
+        }
+    }
+
+    pub(crate) fn reverse(&mut self) {
+        self.0[0].direction = self.0[0].direction.reversed();
+    }
+
+    /// Cycles the primary sort key through the given columns and the name.
+    pub(crate) fn next(&mut self, columns: &[Column]) {
+        let mut columns: Vec<SortColumn> =
+            columns.iter().copied().map(SortColumn::Column).collect();
+        columns.push(SortColumn::Name);
+        let next = match columns
+            .iter()
+            .position(|column| *column == self.primary().column)
+        {
+            Some(i) => columns[(i + 1) % columns.len()],
+            None => columns[0],
+        };
+        self.set_primary(SortKey::new(next));
+    }
+}
+
+impl FromStr for Sorting {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Sorting, String> {
+        let keys = s
+            .split(',')
+            .map(|key| {
+                let (column, direction) = match key.trim().split_once(':') {
+                    Some((column, direction)) => (column, Some(direction)),
+                    None => (key.trim(), None),
+                };
+                let column: SortColumn = column.parse()?;
+                let direction = match direction {
+                    None => column.default_direction(),
+                    Some("asc") => Direction::Ascending,
+                    Some("desc") => Direction::Descending,
+                    Some(direction) => {
+                        return Err(format!(
+                            "invalid sort direction: {} (expected asc or desc)",
+                            direction
+                        ))
+                    }
+                };
+                Ok(SortKey { column, direction })
+            })
+            .collect::<Result<Vec<SortKey>, String>>()?;
+        Ok(Sorting(keys))
+    }
+}
+
+impl TryFrom<String> for Sorting {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Sorting, String> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for Sorting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, key) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{}", key.column)?;
+            if key.direction != key.column.default_direction() {
+                match key.direction {
+                    Direction::Ascending => write!(f, ":asc")?,
+                    Direction::Descending => write!(f, ":desc")?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl From<Sorting> for String {
+    fn from(sorting: Sorting) -> String {
+        sorting.to_string()
+    }
+}
+
+/// A process as sent over the wire by `porc agent`.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct ProcessInfo {
+    pid: u32,
+    parent: Option<u32>,
+    name: String,
+    arguments: Vec<String>,
+    cpu: f32,
+    ram: u64,
+    cgroup: Option<String>,
+    user: Option<String>,
+    namespace_pid: Option<u32>,
+    /// missing in snapshots of older agents
+    #[serde(default)]
+    stopped: bool,
+}
+
+impl From<&Process> for ProcessInfo {
+    fn from(process: &Process) -> ProcessInfo {
+        let pid = |id: ProcessId| id.pid().map(Pid::as_u32);
+        ProcessInfo {
+            pid: pid(process.id).unwrap_or_default(),
+            parent: process.parent.and_then(pid),
+            name: process.name.clone(),
+            arguments: process.arguments.clone(),
+            cpu: process.own_cpu,
+            ram: process.own_ram,
+            cgroup: process.cgroup.clone(),
+            user: process.user.clone(),
+            namespace_pid: process.namespace_pid,
+            stopped: process.stopped,
+        }
+    }
+}
+
+impl Process {
+    pub(crate) fn from_info(info: ProcessInfo, host: usize, host_name: Option<&str>) -> Process {
+        Process {
+            id: ProcessId::Remote(host, Pid::from_u32(info.pid)),
+            name: info.name,
+            arguments: info.arguments,
+            parent: info
+                .parent
+                .map(|parent| ProcessId::Remote(host, Pid::from_u32(parent))),
+            cpu: info.cpu,
+            ram: info.ram,
+            own_cpu: info.cpu,
+            own_ram: info.ram,
+            container: info.cgroup.as_deref().and_then(Container::from_cgroup),
+            cgroup: info.cgroup,
+            user: info.user,
+            namespace_pid: info.namespace_pid,
+            count: 1,
+            merged: Vec::new(),
+            host: host_name.map(str::to_string),
+            stopped: info.stopped,
+        }
+    }
+}
+
+/// A machine running `porc agent`.
 #[derive(Debug)]
-pub(crate) struct ProcessWatcher(ProcessWatcherInner);
+struct Host {
+    /// only set when watching several hosts
+    name: Option<String>,
+    connection: Connection,
+    capacity: Capacity,
+    processes: Vec<Process>,
+    /// why the last refresh failed, the processes are from before that
+    error: Option<String>,
+}
+
+impl Host {
+    fn refresh(&mut self, index: usize) {
+        match self.connection.snapshot() {
+            Ok((capacity, processes)) => {
+                self.capacity = capacity;
+                self.processes = processes
+                    .into_iter()
+                    .map(|info| Process::from_info(info, index, self.name.as_deref()))
+                    .collect();
+                self.error = None;
+            }
+            Err(error) => {
+                self.error = Some(match &self.name {
+                    Some(name) => format!("{}: {}", name, error),
+                    None => error.to_string(),
+                });
+            }
+        }
+    }
+}
+
+/// What sysinfo doesn't know about a process. Reading it from `/proc` for
+/// every process on every refresh is too slow, so it's only read for new
+/// processes.
+#[derive(Debug)]
+struct ProcfsInfo {
+    /// tells processes apart when pids get reused
+    start_time: u64,
+    cgroup: Option<String>,
+    namespace_pid: Option<u32>,
+}
+
+impl ProcfsInfo {
+    fn update_cache(system: &sysinfo::System, cache: &mut HashMap<Pid, ProcfsInfo>) {
+        let processes = system.processes();
+        cache.retain(|pid, info| {
+            processes
+                .get(pid)
+                .is_some_and(|process| process.start_time() == info.start_time)
+        });
+        for (pid, process) in processes {
+            cache.entry(*pid).or_insert_with(|| ProcfsInfo {
+                start_time: process.start_time(),
+                cgroup: read_cgroup(*pid),
+                namespace_pid: read_namespace_pid(*pid),
+            });
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ProcessWatcher(ProcessWatcherInner);
 
 #[derive(Debug)]
 enum ProcessWatcherInner {
     Production {
-        system: sysinfo::System,
+        system: Box<sysinfo::System>,
+        users: sysinfo::Users,
+        procfs: HashMap<Pid, ProcfsInfo>,
     },
+    /// Gets its processes from one or more `porc agent`s.
+    Remote { hosts: Vec<Host> },
     #[cfg(test)]
     TestWatcher {
         processes: Vec<Process>,
+        capacity: Capacity,
+        signals: Vec<(Pid, Signal)>,
+        schedulings: Vec<(Pid, Scheduling)>,
     },
 }
 
 impl ProcessWatcher {
-    pub(crate) fn new(system: sysinfo::System) -> ProcessWatcher {
-        ProcessWatcher(ProcessWatcherInner::Production { system })
+    /// Watches the processes of the local machine. Cpu usage is measured
+    /// between two calls to `refresh`.
+    pub fn new(mut system: sysinfo::System) -> ProcessWatcher {
+        system.refresh_cpu();
+        system.refresh_memory();
+        let mut procfs = HashMap::new();
+        ProcfsInfo::update_cache(&system, &mut procfs);
+        ProcessWatcher(ProcessWatcherInner::Production {
+            system: Box::new(system),
+            users: sysinfo::Users::new_with_refreshed_list(),
+            procfs,
+        })
     }
 
-    pub(crate) fn refresh(&mut self) {
+    /// Watches the processes of the given agents. With several agents every
+    /// host gets a root node.
+    pub(crate) fn connect(addresses: &[Address]) -> R<ProcessWatcher> {
+        let connections = addresses
+            .iter()
+            .map(|address| Ok((address.to_string(), Connection::connect(address)?)))
+            .collect::<R<Vec<_>>>()?;
+        ProcessWatcher::from_connections(connections)
+    }
+
+    fn from_connections(connections: Vec<(String, Connection)>) -> R<ProcessWatcher> {
+        let several = connections.len() > 1;
+        let mut hosts = connections
+            .into_iter()
+            .map(|(name, connection)| Host {
+                name: several.then_some(name),
+                connection,
+                capacity: Capacity { cpu: 0.0, ram: 0 },
+                processes: Vec::new(),
+                error: None,
+            })
+            .collect::<Vec<_>>();
+        for (index, host) in hosts.iter_mut().enumerate() {
+            host.refresh(index);
+        }
+        // failing hosts are only worth watching while others work
+        if let Some(error) = hosts
+            .iter()
+            .map(|host| host.error.clone())
+            .collect::<Option<Vec<_>>>()
+            .and_then(|errors| errors.into_iter().next())
+        {
+            return Err(error.into());
+        }
+        Ok(ProcessWatcher(ProcessWatcherInner::Remote { hosts }))
+    }
+
+    pub fn capacity(&self) -> Capacity {
+        match self {
+            ProcessWatcher(ProcessWatcherInner::Production { system, .. }) => Capacity {
+                cpu: 100.0 * system.cpus().len() as f32,
+                ram: system.total_memory(),
+            },
+            // all hosts together
+            ProcessWatcher(ProcessWatcherInner::Remote { hosts }) => Capacity {
+                cpu: hosts.iter().map(|host| host.capacity.cpu).sum(),
+                ram: hosts.iter().map(|host| host.capacity.ram).sum(),
+            },
+            #[cfg(test)]
+            ProcessWatcher(ProcessWatcherInner::TestWatcher { capacity, .. }) => *capacity,
+        }
+    }
+
+    pub fn refresh(&mut self) -> R<()> {
         match self {
-            ProcessWatcher(ProcessWatcherInner::Production { system }) => system
-                .refresh_processes_specifics(
+            ProcessWatcher(ProcessWatcherInner::Production { system, procfs, .. }) => {
+                system.refresh_processes_specifics(
                     ProcessRefreshKind::new()
                         .with_memory()
                         .with_cpu()
-                        .with_cmd(UpdateKind::OnlyIfNotSet),
-                ),
+                        .with_cmd(UpdateKind::OnlyIfNotSet)
+                        .with_user(UpdateKind::OnlyIfNotSet),
+                );
+                ProcfsInfo::update_cache(system, procfs);
+            }
+            ProcessWatcher(ProcessWatcherInner::Remote { hosts }) => {
+                for (index, host) in hosts.iter_mut().enumerate() {
+                    host.refresh(index);
+                }
+            }
             #[cfg(test)]
             ProcessWatcher(ProcessWatcherInner::TestWatcher { .. }) => {}
         }
+        Ok(())
     }
 
-    pub(crate) fn get_forest(&self) -> Forest<Process> {
+    /// Why hosts couldn't be refreshed. Their processes are shown as they
+    /// were before.
+    pub fn errors(&self) -> Vec<String> {
         match self {
-            ProcessWatcher(ProcessWatcherInner::Production { system }) => Forest::new_forest(
-                system
-                    .processes()
-                    .values()
-                    .filter(|process| process.thread_kind() != Some(ThreadKind::Userland))
-                    .map(Process::from_sysinfo_process),
-            ),
+            ProcessWatcher(ProcessWatcherInner::Remote { hosts }) => {
+                hosts.iter().filter_map(|host| host.error.clone()).collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn processes(&self) -> Vec<Process> {
+        match self {
+            ProcessWatcher(ProcessWatcherInner::Production {
+                system,
+                users,
+                procfs,
+            }) => system
+                .processes()
+                .values()
+                .filter(|process| process.thread_kind() != Some(ThreadKind::Userland))
+                .map(|process| {
+                    Process::from_sysinfo_process(process, users, procfs.get(&process.pid()))
+                })
+                .collect(),
+            ProcessWatcher(ProcessWatcherInner::Remote { hosts }) => hosts
+                .iter()
+                .flat_map(|host| host.processes.iter().cloned())
+                .collect(),
             #[cfg(test)]
-            ProcessWatcher(ProcessWatcherInner::TestWatcher { processes }) => {
-                Forest::new_forest(processes.iter().cloned())
+            ProcessWatcher(ProcessWatcherInner::TestWatcher { processes, .. }) => processes.clone(),
+        }
+    }
+
+    /// The processes of the last `refresh`, arranged by `group_by`. Processes
+    /// can exit while the snapshot gets taken, so their children's parents
+    /// may be missing, see [`Forest::build`].
+    pub fn get_forest(&self, group_by: GroupBy) -> (Forest<Process>, Vec<Anomaly<ProcessId>>) {
+        Forest::build(group_by.group(self.processes()).into_iter())
+    }
+
+    /// Like `get_forest`, but applies the processes of the last `refresh` to a
+    /// forest from an earlier call. Only the subtrees of processes that are
+    /// new or got a new parent are rebuilt.
+    pub fn update_forest(
+        &self,
+        forest: &mut Forest<Process>,
+        group_by: GroupBy,
+    ) -> Vec<Anomaly<ProcessId>> {
+        forest.update(group_by.group(self.processes()).into_iter())
+    }
+
+    /// Sends the signal to the host the process runs on. Group nodes don't
+    /// correspond to processes, so they can't be signalled.
+    pub fn send_signal(&mut self, id: ProcessId, signal: Signal) -> R<()> {
+        match (&mut self.0, id) {
+            (_, ProcessId::Group(_)) => return Err("groups cannot be signalled".into()),
+            (ProcessWatcherInner::Production { .. }, ProcessId::Pid(pid)) => {
+                nix::sys::signal::kill(
+                    nix::unistd::Pid::from_raw(pid.as_u32().try_into()?),
+                    signal,
+                )?;
+            }
+            (ProcessWatcherInner::Remote { hosts }, ProcessId::Remote(host, pid)) => {
+                hosts
+                    .get_mut(host)
+                    .ok_or_else(|| format!("unknown host: {}", host))?
+                    .connection
+                    .send_signal(pid, signal)?;
             }
+            #[cfg(test)]
+            (ProcessWatcherInner::TestWatcher { signals, .. }, id) => {
+                signals.extend(id.pid().map(|pid| (pid, signal)));
+            }
+            (_, id) => return Err(format!("unknown process: {:?}", id).into()),
         }
+        Ok(())
+    }
+
+    /// Like `send_signal`, but changes the nice value, I/O priority or cpu
+    /// affinity of the process.
+    pub(crate) fn schedule(&mut self, id: ProcessId, scheduling: &Scheduling) -> R<()> {
+        match (&mut self.0, id) {
+            (_, ProcessId::Group(_)) => return Err("groups cannot be rescheduled".into()),
+            (ProcessWatcherInner::Production { .. }, ProcessId::Pid(pid)) => {
+                scheduling.apply(pid)?;
+            }
+            (ProcessWatcherInner::Remote { hosts }, ProcessId::Remote(host, pid)) => {
+                hosts
+                    .get_mut(host)
+                    .ok_or_else(|| format!("unknown host: {}", host))?
+                    .connection
+                    .schedule(pid, scheduling)?;
+            }
+            #[cfg(test)]
+            (ProcessWatcherInner::TestWatcher { schedulings, .. }, id) => {
+                schedulings.extend(id.pid().map(|pid| (pid, scheduling.clone())));
+            }
+            (_, id) => return Err(format!("unknown process: {:?}", id).into()),
+        }
+        Ok(())
     }
 }
 
 #[cfg(test)]
 pub(crate) mod test {
     use super::*;
+    use std::thread::JoinHandle;
+
+    #[test]
+    fn procfs_info_is_only_read_for_new_processes() {
+        let mut system = sysinfo::System::new();
+        system.refresh_processes_specifics(ProcessRefreshKind::new());
+        let own_pid = Pid::from_u32(std::process::id());
+        let start_time = system.process(own_pid).unwrap().start_time();
+        let mut cache = HashMap::from([
+            (
+                own_pid,
+                ProcfsInfo {
+                    start_time,
+                    cgroup: Some("cached".to_string()),
+                    namespace_pid: None,
+                },
+            ),
+            (
+                Pid::from_u32(u32::MAX),
+                ProcfsInfo {
+                    start_time: 0,
+                    cgroup: None,
+                    namespace_pid: None,
+                },
+            ),
+        ]);
+        ProcfsInfo::update_cache(&system, &mut cache);
+        assert_eq!(cache[&own_pid].cgroup.as_deref(), Some("cached"));
+        assert!(!cache.contains_key(&Pid::from_u32(u32::MAX)));
+        assert_eq!(cache.len(), system.processes().len());
+    }
 
     impl Process {
         pub(crate) fn fake(pid: usize, cpu: f32, parent: Option<usize>) -> Process {
+            Process::fake_named(pid, crate::utils::test::render_number(pid), cpu, parent)
+        }
+
+        pub(crate) fn fake_named(
+            pid: usize,
+            name: &str,
+            cpu: f32,
+            parent: Option<usize>,
+        ) -> Process {
             Process {
-                pid: pid.into(),
-                name: crate::utils::test::render_number(pid).to_string(),
+                id: ProcessId::Pid(pid.into()),
+                name: name.to_string(),
                 arguments: Vec::new(),
-                parent: parent.map(From::from),
+                parent: parent.map(|parent| ProcessId::Pid(parent.into())),
                 cpu,
                 ram: 0,
+                own_cpu: cpu,
+                own_ram: 0,
+                container: None,
+                cgroup: None,
+                user: None,
+                namespace_pid: None,
+                count: 1,
+                merged: Vec::new(),
+                host: None,
+                stopped: false,
+            }
+        }
+
+        pub(crate) fn with_cgroup(self, cgroup: &str) -> Process {
+            Process {
+                cgroup: Some(cgroup.to_string()),
+                ..self
+            }
+        }
+
+        pub(crate) fn with_stopped(self) -> Process {
+            Process {
+                stopped: true,
+                ..self
+            }
+        }
+
+        pub(crate) fn with_namespace_pid(self, pid: u32) -> Process {
+            Process {
+                namespace_pid: Some(pid),
+                ..self
+            }
+        }
+
+        /// Moves the process to the host with the given index.
+        pub(crate) fn with_host(self, index: usize, host: &str) -> Process {
+            let remote = |id: ProcessId| match id {
+                ProcessId::Pid(pid) => ProcessId::Remote(index, pid),
+                id => id,
+            };
+            Process {
+                id: remote(self.id),
+                parent: self.parent.map(remote),
+                host: Some(host.to_string()),
+                ..self
+            }
+        }
+
+        pub(crate) fn with_user(self, user: &str) -> Process {
+            Process {
+                user: Some(user.to_string()),
+                ..self
+            }
+        }
+
+        pub(crate) fn with_container(self, id: &str) -> Process {
+            Process {
+                container: Some(Container {
+                    runtime: crate::cgroup::Runtime::Docker,
+                    id: id.to_string(),
+                }),
+                ..self
             }
         }
     }
 
+    impl From<usize> for ProcessId {
+        fn from(pid: usize) -> ProcessId {
+            ProcessId::Pid(pid.into())
+        }
+    }
+
     impl ProcessWatcher {
         pub(crate) fn fake(processes: Vec<Process>) -> ProcessWatcher {
-            ProcessWatcher(ProcessWatcherInner::TestWatcher { processes })
+            ProcessWatcher(ProcessWatcherInner::TestWatcher {
+                processes,
+                capacity: Capacity {
+                    cpu: 400.0,
+                    ram: 16 * 2_u64.pow(30),
+                },
+                signals: Vec::new(),
+                schedulings: Vec::new(),
+            })
+        }
+
+        /// Watches fake agents, one per host. The agents' watchers get
+        /// returned when this watcher is dropped.
+        pub(crate) fn fake_hosts(
+            hosts: Vec<(&str, Vec<Process>)>,
+        ) -> R<(ProcessWatcher, Vec<JoinHandle<ProcessWatcher>>)> {
+            ProcessWatcher::fake_hosts_with_failing(hosts, &[])
+        }
+
+        /// Like `fake_hosts`, plus hosts whose agents can't be reached.
+        pub(crate) fn fake_hosts_with_failing(
+            hosts: Vec<(&str, Vec<Process>)>,
+            failing: &[&str],
+        ) -> R<(ProcessWatcher, Vec<JoinHandle<ProcessWatcher>>)> {
+            let mut connections = Vec::new();
+            let mut agents = Vec::new();
+            for (name, processes) in hosts {
+                let (connection, agent) = crate::agent::test::fake_agent(processes);
+                connections.push((name.to_string(), connection));
+                agents.push(agent);
+            }
+            for name in failing {
+                connections.push((name.to_string(), crate::agent::test::failing_agent()?));
+            }
+            Ok((ProcessWatcher::from_connections(connections)?, agents))
+        }
+
+        pub(crate) fn set_processes(&mut self, new_processes: Vec<Process>) {
+            if let ProcessWatcher(ProcessWatcherInner::TestWatcher { processes, .. }) = self {
+                *processes = new_processes;
+            }
+        }
+
+        pub(crate) fn sent_signals(&self) -> &[(Pid, Signal)] {
+            match self {
+                ProcessWatcher(ProcessWatcherInner::TestWatcher { signals, .. }) => signals,
+                _ => &[],
+            }
+        }
+
+        pub(crate) fn schedulings(&self) -> &[(Pid, Scheduling)] {
+            match self {
+                ProcessWatcher(ProcessWatcherInner::TestWatcher { schedulings, .. }) => schedulings,
+                _ => &[],
+            }
         }
     }
 }
@@ -1,40 +1,98 @@
 use crate::R;
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum FilterMode {
+    #[default]
+    Regex,
+    Literal,
+}
 
 #[derive(Debug)]
 pub(crate) enum Regex {
-    Regex { regex: regex::Regex },
-    Invalid { regex: String },
+    Regex {
+        pattern: String,
+        mode: FilterMode,
+        regex: regex::Regex,
+    },
+    Invalid {
+        pattern: String,
+        mode: FilterMode,
+    },
 }
 
 impl Regex {
-    pub(crate) fn empty() -> R<Regex> {
-        Ok(Regex::new(::regex::Regex::new("")?))
+    /// Fails on invalid patterns, unlike `Regex::modify`.
+    pub(crate) fn parse(pattern: &str, mode: FilterMode) -> R<Regex> {
+        let regex = match mode {
+            FilterMode::Regex => regex::Regex::new(pattern)?,
+            FilterMode::Literal => regex::Regex::new(&regex::escape(pattern))?,
+        };
+        Ok(Regex::Regex {
+            pattern: pattern.to_string(),
+            mode,
+            regex,
+        })
     }
 
-    pub(crate) fn new(regex: ::regex::Regex) -> Regex {
-        Regex::Regex { regex }
+    pub(crate) fn from_pattern(pattern: String, mode: FilterMode) -> Regex {
+        match Regex::parse(&pattern, mode) {
+            Ok(regex) => regex,
+            Err(_) => Regex::Invalid { pattern, mode },
+        }
     }
 
     pub(crate) fn is_match(&self, s: &str) -> bool {
         match self {
-            Regex::Regex { regex } => regex.is_match(s),
+            Regex::Regex { regex, .. } => regex.is_match(s),
             Regex::Invalid { .. } => false,
         }
     }
 
+    pub(crate) fn is_valid(&self) -> bool {
+        matches!(self, Regex::Regex { .. })
+    }
+
     pub(crate) fn as_str(&self) -> &str {
         match self {
-            Regex::Regex { regex } => regex.as_str(),
-            Regex::Invalid { regex } => regex.as_str(),
+            Regex::Regex { pattern, .. } => pattern.as_str(),
+            Regex::Invalid { pattern, .. } => pattern.as_str(),
         }
     }
 
-    pub(crate) fn modify(&mut self, f: impl FnOnce(&mut String)) {
-        let mut regex: String = self.as_str().to_string();
-        f(&mut regex);
-        *self = match regex::Regex::new(&regex) {
-            Ok(regex) => Regex::Regex { regex },
-            Err(_) => Regex::Invalid { regex },
+    fn mode(&self) -> FilterMode {
+        match self {
+            Regex::Regex { mode, .. } => *mode,
+            Regex::Invalid { mode, .. } => *mode,
         }
     }
+
+    pub(crate) fn modify(&mut self, f: impl FnOnce(&mut String)) {
+        let mut pattern: String = self.as_str().to_string();
+        f(&mut pattern);
+        *self = Regex::from_pattern(pattern, self.mode());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn literal_mode_does_not_interpret_special_characters() -> R<()> {
+        let regex = Regex::parse("a.c", FilterMode::Literal)?;
+        assert!(regex.is_match("xa.cx"));
+        assert!(!regex.is_match("abc"));
+        assert_eq!(regex.as_str(), "a.c");
+        Ok(())
+    }
+
+    #[test]
+    fn modifying_keeps_the_mode() -> R<()> {
+        let mut regex = Regex::parse("", FilterMode::Literal)?;
+        regex.modify(|pattern| pattern.push('('));
+        assert!(regex.is_match("f(x)"));
+        Ok(())
+    }
 }
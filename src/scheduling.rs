@@ -0,0 +1,293 @@
+use crate::R;
+use nix::errno::Errno;
+use nix::sched::{sched_setaffinity, CpuSet};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use sysinfo::Pid;
+
+const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+const IOPRIO_CLASS_SHIFT: u32 = 13;
+
+/// A change to how the kernel schedules a process.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum Scheduling {
+    Nice(i32),
+    IoPriority(IoPriority),
+    /// the cpus the process is allowed to run on
+    Affinity(Vec<usize>),
+}
+
+/// An I/O scheduling class and, except for `Idle`, a level from 0 (highest)
+/// to 7 (lowest), see `ioprio_set(2)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum IoPriority {
+    Realtime(u8),
+    BestEffort(u8),
+    Idle,
+}
+
+impl Scheduling {
+    /// Applies to all threads of the process, like `taskset -a` does. The
+    /// kernel only changes the thread with the given id otherwise.
+    pub(crate) fn apply(&self, pid: Pid) -> R<()> {
+        let raw_pid: libc::c_int = pid.as_u32().try_into()?;
+        for tid in threads(raw_pid) {
+            match self.apply_to_thread(tid) {
+                // threads can exit while we're at it
+                Err(error) if tid != raw_pid && error.downcast_ref() == Some(&Errno::ESRCH) => {}
+                result => result?,
+            }
+        }
+        Ok(())
+    }
+
+    fn apply_to_thread(&self, tid: libc::c_int) -> R<()> {
+        match self {
+            Scheduling::Nice(nice) => {
+                // SAFETY: setpriority only takes plain integers.
+                Errno::result(unsafe {
+                    libc::setpriority(libc::PRIO_PROCESS as _, tid as libc::id_t, *nice)
+                })?;
+            }
+            Scheduling::IoPriority(priority) => {
+                // SAFETY: ioprio_set takes three integers, there's no memory
+                // involved.
+                Errno::result(unsafe {
+                    libc::syscall(
+                        libc::SYS_ioprio_set,
+                        IOPRIO_WHO_PROCESS,
+                        tid,
+                        priority.value(),
+                    )
+                })?;
+            }
+            Scheduling::Affinity(cpus) => {
+                let mut cpu_set = CpuSet::new();
+                for cpu in cpus {
+                    cpu_set
+                        .set(*cpu)
+                        .map_err(|_| format!("invalid cpu: {}", cpu))?;
+                }
+                sched_setaffinity(nix::unistd::Pid::from_raw(tid), &cpu_set)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The thread ids of a process from `/proc/<pid>/task`, or just the pid when
+/// they can't be read.
+fn threads(pid: libc::c_int) -> Vec<libc::c_int> {
+    let mut tids: Vec<libc::c_int> = std::fs::read_dir(format!("/proc/{}/task", pid))
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse().ok())
+        .collect();
+    if tids.is_empty() {
+        tids.push(pid);
+    }
+    tids.sort_unstable();
+    tids
+}
+
+impl IoPriority {
+    fn value(self) -> libc::c_int {
+        let (class, level) = match self {
+            IoPriority::Realtime(level) => (1, level),
+            IoPriority::BestEffort(level) => (2, level),
+            IoPriority::Idle => (3, 0),
+        };
+        class << IOPRIO_CLASS_SHIFT | libc::c_int::from(level)
+    }
+}
+
+/// Parses `idle`, `best-effort:<level>` or `realtime:<level>`, or their
+/// abbreviations `be` and `rt`. The level defaults to 4.
+impl FromStr for IoPriority {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<IoPriority, String> {
+        let (class, level) = match s.trim().split_once(':') {
+            Some((class, level)) => (
+                class,
+                Some(
+                    level
+                        .parse::<u8>()
+                        .ok()
+                        .filter(|level| *level <= 7)
+                        .ok_or_else(|| format!("invalid I/O priority level: {} (0-7)", level))?,
+                ),
+            ),
+            None => (s.trim(), None),
+        };
+        match (class, level) {
+            ("idle", None) => Ok(IoPriority::Idle),
+            ("best-effort" | "be", level) => Ok(IoPriority::BestEffort(level.unwrap_or(4))),
+            ("realtime" | "rt", level) => Ok(IoPriority::Realtime(level.unwrap_or(4))),
+            _ => Err(format!(
+                "invalid I/O priority: {} (expected idle, be:<0-7> or rt:<0-7>)",
+                s
+            )),
+        }
+    }
+}
+
+pub(crate) fn parse_nice(s: &str) -> Result<i32, String> {
+    s.trim()
+        .parse()
+        .ok()
+        .filter(|nice| (-20..=19).contains(nice))
+        .ok_or_else(|| format!("invalid nice value: {} (-20 to 19)", s))
+}
+
+/// Parses cpu lists like `0-3,6`.
+pub(crate) fn parse_cpu_list(s: &str) -> Result<Vec<usize>, String> {
+    let invalid = || format!("invalid cpu list: {} (e.g. 0-3,6)", s);
+    let mut cpus = Vec::new();
+    for range in s.split(',') {
+        let (first, last) = match range.trim().split_once('-') {
+            Some((first, last)) => (first, last),
+            None => (range.trim(), range.trim()),
+        };
+        let first: usize = first.parse().map_err(|_| invalid())?;
+        let last: usize = last.parse().map_err(|_| invalid())?;
+        if first > last {
+            return Err(invalid());
+        }
+        cpus.extend(first..=last);
+    }
+    cpus.sort_unstable();
+    cpus.dedup();
+    Ok(cpus)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn parses_nice_values() {
+        assert_eq!(parse_nice("10"), Ok(10));
+        assert_eq!(parse_nice(" -5 "), Ok(-5));
+        assert!(parse_nice("20").is_err());
+        assert!(parse_nice("low").is_err());
+    }
+
+    #[test]
+    fn parses_io_priorities() {
+        assert_eq!("idle".parse(), Ok(IoPriority::Idle));
+        assert_eq!("be:7".parse(), Ok(IoPriority::BestEffort(7)));
+        assert_eq!("best-effort".parse(), Ok(IoPriority::BestEffort(4)));
+        assert_eq!("rt:0".parse(), Ok(IoPriority::Realtime(0)));
+        assert!("be:8".parse::<IoPriority>().is_err());
+        assert!("idle:3".parse::<IoPriority>().is_err());
+        assert!("fast".parse::<IoPriority>().is_err());
+    }
+
+    #[test]
+    fn encodes_io_priorities_like_the_kernel() {
+        assert_eq!(IoPriority::BestEffort(4).value(), 0x4004);
+        assert_eq!(IoPriority::Idle.value(), 0x6000);
+    }
+
+    /// Runs in a child process of `applies_to_all_threads`: starts a few
+    /// threads, says so on stdout and waits to get killed.
+    #[test]
+    #[ignore]
+    fn process_with_threads() {
+        if std::env::var_os("PORC_TEST_CHILD").is_none() {
+            return;
+        }
+        for _ in 0..3 {
+            std::thread::spawn(|| std::thread::sleep(std::time::Duration::from_secs(60)));
+        }
+        println!("ready");
+        std::thread::sleep(std::time::Duration::from_secs(60));
+    }
+
+    /// Kills the child process when dropped, also when an assertion fails.
+    struct KillOnDrop(std::process::Child);
+
+    impl Drop for KillOnDrop {
+        fn drop(&mut self) {
+            let _ = self.0.kill();
+            let _ = self.0.wait();
+        }
+    }
+
+    fn affinity(tid: libc::c_int) -> R<Vec<usize>> {
+        let cpu_set = nix::sched::sched_getaffinity(nix::unistd::Pid::from_raw(tid))?;
+        Ok((0..CpuSet::count())
+            .filter(|cpu| cpu_set.is_set(*cpu).unwrap_or(false))
+            .collect())
+    }
+
+    /// Reads the nice value of a thread from its stat file.
+    fn nice(pid: libc::c_int, tid: libc::c_int) -> R<i32> {
+        let stat = std::fs::read_to_string(format!("/proc/{}/task/{}/stat", pid, tid))?;
+        // the fields after the parenthesized command name, starting with the state
+        let fields: Vec<&str> = stat
+            .rsplit_once(')')
+            .ok_or("invalid stat file")?
+            .1
+            .split_whitespace()
+            .collect();
+        Ok(fields.get(16).ok_or("invalid stat file")?.parse()?)
+    }
+
+    #[test]
+    fn applies_to_all_threads() -> R<()> {
+        use std::io::BufRead;
+        let mut child = KillOnDrop(
+            std::process::Command::new(std::env::current_exe()?)
+                .args([
+                    "scheduling::test::process_with_threads",
+                    "--exact",
+                    "--ignored",
+                    "--nocapture",
+                    "--test-threads=1",
+                ])
+                .env("PORC_TEST_CHILD", "1")
+                .stdout(std::process::Stdio::piped())
+                .spawn()?,
+        );
+        let stdout = child.0.stdout.take().ok_or("child has no stdout")?;
+        let mut lines = std::io::BufReader::new(stdout).lines();
+        // libtest prints the name of the test on the same line
+        while !lines
+            .next()
+            .transpose()?
+            .ok_or("child exited early")?
+            .ends_with("ready")
+        {}
+        let pid: libc::c_int = child.0.id().try_into()?;
+        let tids = threads(pid);
+        assert!(tids.len() > 3);
+        let cpus = affinity(pid)?;
+        let last_cpu = vec![*cpus.last().ok_or("no cpus")?];
+        Scheduling::Affinity(last_cpu.clone()).apply(Pid::from_u32(child.0.id()))?;
+        // changes nothing on machines with a single cpu, unlike the nice value
+        Scheduling::Nice(19).apply(Pid::from_u32(child.0.id()))?;
+        for tid in tids {
+            assert_eq!((affinity(tid)?, nice(pid, tid)?), (last_cpu.clone(), 19));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn lists_the_pid_for_processes_without_threads() {
+        assert_eq!(threads(libc::c_int::MAX), vec![libc::c_int::MAX]);
+    }
+
+    #[test]
+    fn parses_cpu_lists() {
+        assert_eq!(parse_cpu_list("0-3,6"), Ok(vec![0, 1, 2, 3, 6]));
+        assert_eq!(parse_cpu_list("2, 1,2"), Ok(vec![1, 2]));
+        assert!(parse_cpu_list("").is_err());
+        assert!(parse_cpu_list("3-1").is_err());
+        assert!(parse_cpu_list("a-b").is_err());
+    }
+}
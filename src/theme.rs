@@ -0,0 +1,165 @@
+use ratatui::style::Color;
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Theme {
+    /// separators and the rule below the header
+    pub(crate) lines: Color,
+    /// tree prefixes, e.g. `├──`
+    pub(crate) tree: Color,
+    pub(crate) selected_process: Color,
+    pub(crate) status_bar: Color,
+    pub(crate) status_bar_editing: Color,
+    pub(crate) status_bar_selected: Color,
+    /// the marker of stopped processes
+    pub(crate) stopped: Color,
+    /// heat map colors, from low to high load
+    pub(crate) heat: [Color; 4],
+}
+
+/// Loads below this fraction of the capacity don't get colored.
+const HEAT_THRESHOLD: f32 = 0.1;
+
+impl Theme {
+    pub(crate) fn heat_color(&self, load: f32) -> Option<Color> {
+        if load < HEAT_THRESHOLD {
+            None
+        } else {
+            let index = (load * self.heat.len() as f32) as usize;
+            Some(self.heat[index.min(self.heat.len() - 1)])
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum HeatMap {
+    #[default]
+    Off,
+    /// relative to the number of cores and the total memory
+    System,
+    /// relative to the maximum of the visible processes
+    View,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum BuiltinTheme {
+    #[default]
+    Dark,
+    Light,
+    HighContrast,
+    Monochrome,
+}
+
+impl BuiltinTheme {
+    pub(crate) fn theme(self) -> Theme {
+        match self {
+            BuiltinTheme::Dark => Theme {
+                lines: Color::DarkGray,
+                tree: Color::Blue,
+                selected_process: Color::Red,
+                status_bar: Color::Reset,
+                status_bar_editing: Color::Yellow,
+                status_bar_selected: Color::Red,
+                stopped: Color::Yellow,
+                heat: [Color::Green, Color::Yellow, Color::LightRed, Color::Red],
+            },
+            BuiltinTheme::Light => Theme {
+                lines: Color::Black,
+                tree: Color::Magenta,
+                selected_process: Color::Red,
+                status_bar: Color::Reset,
+                status_bar_editing: Color::Blue,
+                status_bar_selected: Color::Red,
+                stopped: Color::Blue,
+                heat: [Color::Green, Color::Blue, Color::Magenta, Color::Red],
+            },
+            BuiltinTheme::HighContrast => Theme {
+                lines: Color::White,
+                tree: Color::LightCyan,
+                selected_process: Color::LightRed,
+                status_bar: Color::White,
+                status_bar_editing: Color::LightYellow,
+                status_bar_selected: Color::LightRed,
+                stopped: Color::LightYellow,
+                heat: [
+                    Color::LightGreen,
+                    Color::LightYellow,
+                    Color::LightMagenta,
+                    Color::LightRed,
+                ],
+            },
+            BuiltinTheme::Monochrome => Theme {
+                lines: Color::Reset,
+                tree: Color::Reset,
+                selected_process: Color::Reset,
+                status_bar: Color::Reset,
+                status_bar_editing: Color::Reset,
+                status_bar_selected: Color::Reset,
+                stopped: Color::Reset,
+                heat: [Color::Reset; 4],
+            },
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        BuiltinTheme::default().theme()
+    }
+}
+
+/// A theme defined in the config file, e.g.:
+///
+/// ```toml
+/// [themes.solarized]
+/// base = "light"
+/// tree = "#268bd2"
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub(crate) struct CustomTheme {
+    base: BuiltinTheme,
+    lines: Option<Color>,
+    tree: Option<Color>,
+    selected_process: Option<Color>,
+    status_bar: Option<Color>,
+    status_bar_editing: Option<Color>,
+    status_bar_selected: Option<Color>,
+    stopped: Option<Color>,
+    heat: Option<[Color; 4]>,
+}
+
+impl CustomTheme {
+    pub(crate) fn theme(&self) -> Theme {
+        let base = self.base.theme();
+        Theme {
+            lines: self.lines.unwrap_or(base.lines),
+            tree: self.tree.unwrap_or(base.tree),
+            selected_process: self.selected_process.unwrap_or(base.selected_process),
+            status_bar: self.status_bar.unwrap_or(base.status_bar),
+            status_bar_editing: self.status_bar_editing.unwrap_or(base.status_bar_editing),
+            status_bar_selected: self.status_bar_selected.unwrap_or(base.status_bar_selected),
+            stopped: self.stopped.unwrap_or(base.stopped),
+            heat: self.heat.unwrap_or(base.heat),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn heat_colors() {
+        let theme = BuiltinTheme::Dark.theme();
+        assert_eq!(theme.heat_color(0.05), None);
+        assert_eq!(theme.heat_color(0.1), Some(Color::Green));
+        assert_eq!(theme.heat_color(0.3), Some(Color::Yellow));
+        assert_eq!(theme.heat_color(0.6), Some(Color::LightRed));
+        assert_eq!(theme.heat_color(0.9), Some(Color::Red));
+        assert_eq!(theme.heat_color(1.0), Some(Color::Red));
+        assert_eq!(theme.heat_color(3.0), Some(Color::Red));
+    }
+}
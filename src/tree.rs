@@ -1,58 +1,288 @@
 use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::collections::hash_map::Entry;
 use std::collections::VecDeque;
+use std::collections::{HashMap, HashSet};
 use std::fmt::Debug;
-use std::fmt::Display;
 use std::hash::Hash;
 
-pub(crate) trait Node {
+/// Something that can be arranged in a [`Forest`], e.g. a process.
+pub trait Node {
     type Id;
 
     fn id(&self) -> Self::Id;
 
+    /// Nodes without a parent become roots of the forest.
     fn parent(&self) -> Option<Self::Id>;
 
+    /// Gets called on every node with each of its children after building a
+    /// forest, bottom up, e.g. to sum up the resource usage of subtrees.
     fn accumulate_from(&mut self, other: &Self);
 }
 
-#[derive(Debug)]
-pub(crate) struct Tree<Node> {
+#[derive(Debug, Clone)]
+pub struct Tree<Node> {
     node: Node,
     children: Forest<Node>,
 }
 
+#[derive(Debug, Clone)]
+pub struct Forest<Node>(Vec<Tree<Node>>);
+
+/// A node in display order, see [`Forest::into_rows`].
 #[derive(Debug)]
-pub(crate) struct Forest<Node>(Vec<Tree<Node>>);
+pub struct Row<Node: crate::tree::Node> {
+    pub node: Node,
+    /// the node this one is shown under
+    pub parent: Option<Node::Id>,
+    parent_row: Option<usize>,
+    is_last: bool,
+    has_children: bool,
+}
+
+impl<Node: crate::tree::Node> Row<Node> {
+    /// The index of the row this one is shown under.
+    pub fn parent_row(&self) -> Option<usize> {
+        self.parent_row
+    }
+
+    /// The prefix that draws the tree, e.g. `│ ├─┬ `. Only looks at the
+    /// ancestors of the row, so rendering a few rows of a big forest is cheap.
+    pub fn prefix(&self, rows: &[Row<Node>]) -> String {
+        if self.parent_row.is_none() {
+            return String::new();
+        }
+        let mut parts = vec![
+            if self.has_children { "┬ " } else { "─ " },
+            if self.is_last { "└─" } else { "├─" },
+        ];
+        let mut ancestor = self.parent_row.and_then(|index| rows.get(index));
+        while let Some(row) = ancestor {
+            if row.parent_row.is_none() {
+                break;
+            }
+            parts.push(if row.is_last { "  " } else { "│ " });
+            ancestor = row.parent_row.and_then(|index| rows.get(index));
+        }
+        parts.into_iter().rev().collect()
+    }
+}
+
+/// Inconsistencies in the input of [`Forest::build`] and how they were
+/// resolved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Anomaly<Id> {
+    /// The parent isn't part of the input, so the node became a root.
+    MissingParent { id: Id, parent: Id },
+    /// The node is part of a cycle of parents and became a root to break it.
+    Cycle { id: Id },
+    /// Another node with the same id came earlier in the input, so this one
+    /// got dropped.
+    DuplicateId { id: Id },
+}
+
+impl<Node> Tree<Node> {
+    pub fn node(&self) -> &Node {
+        &self.node
+    }
+
+    pub fn children(&self) -> &Forest<Node> {
+        &self.children
+    }
+}
+
+impl<Node> Forest<Node> {
+    pub fn trees(&self) -> &[Tree<Node>] {
+        &self.0
+    }
+}
 
 impl<Node> Forest<Node>
 where
-    Node: crate::tree::Node + Display,
+    Node: crate::tree::Node,
     Node::Id: Hash + Eq + Copy + Debug,
 {
-    pub(crate) fn empty() -> Self {
+    pub fn empty() -> Self {
         Forest(Vec::new())
     }
 
-    pub(crate) fn new_forest(input: impl Iterator<Item = Node>) -> Self {
+    /// Builds a forest out of nodes in any order and accumulates every subtree
+    /// into its root. See [`Forest::build`] for how inconsistent input is
+    /// handled.
+    pub fn new_forest(input: impl Iterator<Item = Node>) -> Self {
+        Forest::build(input).0
+    }
+
+    /// Like [`Forest::new_forest`], but also reports inconsistencies in the
+    /// input. Every node ends up in the forest exactly once, except for
+    /// duplicates: nodes with missing parents become roots, and cycles get
+    /// broken by turning one of their nodes into a root.
+    pub fn build(input: impl Iterator<Item = Node>) -> (Self, Vec<Anomaly<Node::Id>>) {
+        let mut anomalies = Vec::new();
+        let (ids, node_map) = Forest::deduplicate(input, &mut anomalies);
+        let mut result = Forest::assemble(ids, node_map, &mut anomalies);
+        result.compute_accumulate();
+        (result, anomalies)
+    }
+
+    /// Applies a new snapshot of all nodes to the forest. Trees whose roots
+    /// still have the same parent are kept and only get their nodes replaced,
+    /// everything else gets rebuilt. Apart from the order of siblings, the
+    /// result is the same as building a new forest out of `input`.
+    pub fn update(&mut self, input: impl Iterator<Item = Node>) -> Vec<Anomaly<Node::Id>> {
+        let mut anomalies = Vec::new();
+        let (ids, mut node_map) = Forest::deduplicate(input, &mut anomalies);
+        let mut kept = HashSet::new();
+        self.keep(None, &mut node_map, &mut kept, &mut anomalies);
+        let ids = ids
+            .into_iter()
+            .filter(|id| node_map.contains_key(id))
+            .collect();
+        let mut rebuilt_anomalies = Vec::new();
+        let rebuilt = Forest::assemble(ids, node_map, &mut rebuilt_anomalies);
+        anomalies.extend(rebuilt_anomalies.into_iter().filter(|anomaly| {
+            !matches!(anomaly, Anomaly::MissingParent { parent, .. } if kept.contains(parent))
+        }));
+        let mut attachments: HashMap<Node::Id, Vec<Tree<Node>>> = HashMap::new();
+        for tree in rebuilt.0 {
+            match tree.node.parent() {
+                Some(parent) if kept.contains(&parent) => {
+                    attachments.entry(parent).or_default().push(tree);
+                }
+                _ => self.0.push(tree),
+            }
+        }
+        self.attach(&mut attachments);
+        self.compute_accumulate();
+        anomalies
+    }
+
+    fn deduplicate(
+        input: impl Iterator<Item = Node>,
+        anomalies: &mut Vec<Anomaly<Node::Id>>,
+    ) -> (Vec<Node::Id>, HashMap<Node::Id, Node>) {
+        let mut ids = Vec::new();
         let mut node_map = HashMap::new();
-        let mut children_map = HashMap::new();
-        let mut roots = Vec::new();
         for node in input {
-            if let Some(parent) = node.parent() {
-                children_map
-                    .entry(parent)
-                    .or_insert(Vec::new())
-                    .push(node.id());
-            } else {
-                roots.push(node.id());
+            let id = node.id();
+            match node_map.entry(id) {
+                Entry::Occupied(_) => anomalies.push(Anomaly::DuplicateId { id }),
+                Entry::Vacant(entry) => {
+                    ids.push(id);
+                    entry.insert(node);
+                }
+            }
+        }
+        (ids, node_map)
+    }
+
+    /// Builds a forest without accumulating, `ids` determines the order.
+    fn assemble(
+        ids: Vec<Node::Id>,
+        mut node_map: HashMap<Node::Id, Node>,
+        anomalies: &mut Vec<Anomaly<Node::Id>>,
+    ) -> Self {
+        let mut children_map: HashMap<Node::Id, Vec<Node::Id>> = HashMap::new();
+        let mut roots = Vec::new();
+        for &id in ids.iter() {
+            match node_map[&id].parent() {
+                Some(parent) if node_map.contains_key(&parent) => {
+                    children_map.entry(parent).or_default().push(id);
+                }
+                Some(parent) => {
+                    anomalies.push(Anomaly::MissingParent { id, parent });
+                    roots.push(id);
+                }
+                None => roots.push(id),
             }
-            node_map.insert(node.id(), node);
         }
         let mut result = Forest::mk_forest(&mut node_map, &mut children_map, roots);
-        result.compute_accumulate();
+        // Nodes that are left over can't be reached from any root, so
+        // following their parents leads into a cycle.
+        for id in ids {
+            if !node_map.contains_key(&id) {
+                continue;
+            }
+            let root = Forest::find_cycle(&node_map, id);
+            if let Some(siblings) = node_map[&root]
+                .parent()
+                .and_then(|parent| children_map.get_mut(&parent))
+            {
+                siblings.retain(|sibling| *sibling != root);
+            }
+            anomalies.push(Anomaly::Cycle { id: root });
+            let mut trees = Forest::mk_forest(&mut node_map, &mut children_map, vec![root]);
+            result.0.append(&mut trees.0);
+        }
         result
     }
 
+    /// Keeps the trees whose roots are still in `node_map` with the same
+    /// parent, replacing their nodes. The nodes of all other trees stay in
+    /// `node_map`.
+    fn keep(
+        &mut self,
+        parent: Option<Node::Id>,
+        node_map: &mut HashMap<Node::Id, Node>,
+        kept: &mut HashSet<Node::Id>,
+        anomalies: &mut Vec<Anomaly<Node::Id>>,
+    ) {
+        for mut tree in std::mem::take(&mut self.0) {
+            let id = tree.node.id();
+            let unchanged = match (node_map.get(&id).map(|node| node.parent()), parent) {
+                (Some(new_parent), Some(_)) => new_parent == parent,
+                (Some(None), None) => true,
+                (Some(Some(new_parent)), None) => {
+                    let missing =
+                        !node_map.contains_key(&new_parent) && !kept.contains(&new_parent);
+                    if missing {
+                        anomalies.push(Anomaly::MissingParent {
+                            id,
+                            parent: new_parent,
+                        });
+                    }
+                    missing
+                }
+                (None, _) => false,
+            };
+            if !unchanged {
+                continue;
+            }
+            if let Some(node) = node_map.remove(&id) {
+                tree.node = node;
+                kept.insert(id);
+                tree.children.keep(Some(id), node_map, kept, anomalies);
+                self.0.push(tree);
+            }
+        }
+    }
+
+    fn attach(&mut self, attachments: &mut HashMap<Node::Id, Vec<Tree<Node>>>) {
+        for tree in self.0.iter_mut() {
+            if attachments.is_empty() {
+                return;
+            }
+            if let Some(mut trees) = attachments.remove(&tree.node.id()) {
+                tree.children.0.append(&mut trees);
+            }
+            tree.children.attach(attachments);
+        }
+    }
+
+    /// Follows the parents of `id` until one of them repeats, which is then
+    /// part of a cycle.
+    fn find_cycle(node_map: &HashMap<Node::Id, Node>, id: Node::Id) -> Node::Id {
+        let mut visited = HashSet::new();
+        let mut current = id;
+        while visited.insert(current) {
+            match node_map.get(&current).and_then(|node| node.parent()) {
+                Some(parent) if node_map.contains_key(&parent) => current = parent,
+                _ => break,
+            }
+        }
+        current
+    }
+
     fn mk_forest(
         node_map: &mut HashMap<Node::Id, Node>,
         children_map: &mut HashMap<Node::Id, Vec<Node::Id>>,
@@ -60,16 +290,20 @@ where
     ) -> Self {
         let mut result = Forest(Vec::new());
         for root in roots.into_iter() {
+            let Some(node) = node_map.remove(&root) else {
+                continue;
+            };
             let children = children_map.remove(&root).unwrap_or_default();
             result.0.push(Tree {
-                node: node_map.remove(&root).unwrap(),
+                node,
                 children: Forest::mk_forest(node_map, children_map, children),
             });
         }
         result
     }
 
-    pub(crate) fn iter(&self) -> impl Iterator<Item = &Node> {
+    /// Iterates over all nodes, depth first.
+    pub fn iter(&self) -> impl Iterator<Item = &Node> {
         struct Iter<'a, Node>(VecDeque<&'a Tree<Node>>);
 
         impl<'a, Node> Iterator for Iter<'a, Node> {
@@ -91,7 +325,7 @@ where
         Iter(self.0.iter().rev().collect())
     }
 
-    pub(crate) fn sort_by<F>(&mut self, compare: &F)
+    pub fn sort_by<F>(&mut self, compare: &F)
     where
         F: Fn(&Node, &Node) -> Ordering,
     {
@@ -110,13 +344,127 @@ where
         }
     }
 
-    pub(crate) fn filter<F>(&mut self, filter: F)
+    /// Merges sibling trees for which `key` returns the same id into a single
+    /// tree, whose children are the children of all merged trees. `key` gets
+    /// passed the parent node, if any.
+    pub fn collapse_siblings<K, M>(&mut self, key: &K, merge: &M)
+    where
+        K: Fn(Option<&Node>, &Node) -> Option<Node::Id>,
+        M: Fn(Node::Id, Vec<Node>) -> Node,
+    {
+        self.collapse_siblings_helper(None, key, merge);
+    }
+
+    fn collapse_siblings_helper<K, M>(&mut self, parent: Option<&Node>, key: &K, merge: &M)
+    where
+        K: Fn(Option<&Node>, &Node) -> Option<Node::Id>,
+        M: Fn(Node::Id, Vec<Node>) -> Node,
+    {
+        let mut groups: Vec<Vec<Tree<Node>>> = Vec::new();
+        let mut indices: HashMap<Node::Id, usize> = HashMap::new();
+        for tree in std::mem::take(&mut self.0) {
+            match key(parent, &tree.node) {
+                Some(id) => match indices.get(&id) {
+                    Some(&index) => groups[index].push(tree),
+                    None => {
+                        indices.insert(id, groups.len());
+                        groups.push(vec![tree]);
+                    }
+                },
+                None => groups.push(vec![tree]),
+            }
+        }
+        for mut trees in groups {
+            let id = key(parent, &trees[0].node);
+            let mut tree = match id {
+                Some(id) if trees.len() > 1 => {
+                    let mut nodes = Vec::new();
+                    let mut children = Vec::new();
+                    for tree in trees {
+                        nodes.push(tree.node);
+                        children.extend(tree.children.0);
+                    }
+                    Tree {
+                        node: merge(id, nodes),
+                        children: Forest(children),
+                    }
+                }
+                _ => trees.remove(0),
+            };
+            tree.children
+                .collapse_siblings_helper(Some(&tree.node), key, merge);
+            self.0.push(tree);
+        }
+    }
+
+    /// The node under which the node with the given id is shown.
+    pub fn parent_of(&self, id: Node::Id) -> Option<&Node> {
+        for tree in self.0.iter() {
+            if tree.children.0.iter().any(|child| child.node.id() == id) {
+                return Some(&tree.node);
+            }
+            if let Some(parent) = tree.children.parent_of(id) {
+                return Some(parent);
+            }
+        }
+        None
+    }
+
+    /// Keeps the nodes that match `filter`, together with all their ancestors
+    /// and descendants.
+    pub fn filter<F>(&mut self, filter: F)
     where
         F: Fn(&Node) -> bool,
     {
         self.filter_helper(&filter, false);
     }
 
+    /// Like [`Forest::filter`], but only clones the nodes that are kept.
+    pub fn filtered<F>(&self, filter: F) -> Self
+    where
+        Node: Clone,
+        F: Fn(&Node) -> bool,
+    {
+        self.filtered_helper(&filter, false)
+    }
+
+    /// The tree of the node with the given id, wherever it is in the forest,
+    /// as a forest of its own. Empty if there's no such node.
+    pub fn subtree(&self, id: Node::Id) -> Self
+    where
+        Node: Clone,
+    {
+        for tree in self.0.iter() {
+            if tree.node.id() == id {
+                return Forest(vec![tree.clone()]);
+            }
+            let subtree = tree.children.subtree(id);
+            if !subtree.0.is_empty() {
+                return subtree;
+            }
+        }
+        Forest::empty()
+    }
+
+    fn filtered_helper<F>(&self, filter: &F, parent_included: bool) -> Self
+    where
+        Node: Clone,
+        F: Fn(&Node) -> bool,
+    {
+        let mut result = Forest::empty();
+        for tree in self.0.iter() {
+            let included = parent_included || filter(&tree.node);
+            let children = tree.children.filtered_helper(filter, included);
+            if included || !children.0.is_empty() {
+                result.0.push(Tree {
+                    node: tree.node.clone(),
+                    children,
+                });
+            }
+        }
+        result
+    }
+
     fn filter_helper<F>(&mut self, filter: &F, parent_included: bool) -> bool
     where
         F: Fn(&Node) -> bool,
@@ -137,7 +485,32 @@ where
         any_child_included
     }
 
-    pub(crate) fn render_forest_prefixes(&self) -> Vec<(String, &Node)> {
+    /// Like [`Forest::render_forest_prefixes`], but takes ownership of the
+    /// nodes and leaves rendering the prefixes to [`Row::prefix`].
+    pub fn into_rows(self) -> Vec<Row<Node>> {
+        let mut acc = Vec::new();
+        self.into_rows_helper(None, &mut acc);
+        acc
+    }
+
+    fn into_rows_helper(self, parent_row: Option<usize>, acc: &mut Vec<Row<Node>>) {
+        let length = self.0.len();
+        let parent = parent_row.map(|index| acc[index].node.id());
+        for (i, child) in self.0.into_iter().enumerate() {
+            let index = acc.len();
+            acc.push(Row {
+                node: child.node,
+                parent,
+                parent_row,
+                is_last: i == length - 1,
+                has_children: !child.children.0.is_empty(),
+            });
+            child.children.into_rows_helper(Some(index), acc);
+        }
+    }
+
+    /// All nodes in display order, with the prefixes that draw the tree.
+    pub fn render_forest_prefixes(&self) -> Vec<(String, &Node)> {
         let mut acc = Vec::new();
         self.render_forest_prefixes_helper(true, &mut Vec::new(), &mut acc);
         acc
@@ -176,6 +549,7 @@ where
 mod test {
     use super::*;
     use pretty_assertions::assert_eq;
+    use std::fmt::Display;
     use unindent::Unindent;
 
     impl<Node> Forest<Node>
@@ -194,7 +568,7 @@ mod test {
         }
     }
 
-    #[derive(Debug)]
+    #[derive(Debug, Clone)]
     struct TestNode {
         id: usize,
         parent: Option<usize>,
@@ -477,6 +851,28 @@ mod test {
                 .unindent()
             );
         }
+
+        #[test]
+        fn g_subtrees() {
+            let tree = Forest::new_forest(
+                vec![
+                    TestNode::new(1, None),
+                    TestNode::new(2, Some(1)),
+                    TestNode::new(3, Some(2)),
+                    TestNode::new(4, Some(1)),
+                ]
+                .into_iter(),
+            );
+            assert_eq!(
+                tree.subtree(2).test_format(),
+                "
+                    two
+                    └── three
+                "
+                .unindent()
+            );
+            assert!(tree.subtree(5).trees().is_empty());
+        }
     }
 
     mod i_accumulation {
@@ -592,6 +988,75 @@ mod test {
         }
     }
 
+    mod j_collapsing {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        /// Collapses siblings with the same parity, merged nodes get the
+        /// smallest id of their parts.
+        fn collapse_by_parity(tree: &mut Forest<TestNode>) {
+            tree.collapse_siblings(
+                &|_parent, node: &TestNode| (node.id > 1).then_some(100 + node.id % 2),
+                &|_id, nodes: Vec<TestNode>| TestNode {
+                    id: nodes.iter().map(|node| node.id).min().unwrap_or(0),
+                    parent: nodes[0].parent,
+                },
+            );
+        }
+
+        #[test]
+        fn a_merges_siblings_with_the_same_key() {
+            let mut tree = Forest::new_forest(
+                vec![
+                    TestNode::new(1, None),
+                    TestNode::new(2, Some(1)),
+                    TestNode::new(3, Some(1)),
+                    TestNode::new(4, Some(1)),
+                    TestNode::new(5, Some(1)),
+                    TestNode::new(6, Some(1)),
+                ]
+                .into_iter(),
+            );
+            collapse_by_parity(&mut tree);
+            assert_eq!(
+                tree.test_format(),
+                "
+                    one
+                    ├── two
+                    └── three
+                "
+                .unindent()
+            );
+        }
+
+        #[test]
+        fn b_merges_the_children_of_merged_nodes() {
+            let mut tree = Forest::new_forest(
+                vec![
+                    TestNode::new(1, None),
+                    TestNode::new(2, Some(1)),
+                    TestNode::new(4, Some(1)),
+                    TestNode::new(5, Some(2)),
+                    TestNode::new(6, Some(4)),
+                    TestNode::new(7, Some(4)),
+                ]
+                .into_iter(),
+            );
+            collapse_by_parity(&mut tree);
+            assert_eq!(
+                tree.test_format(),
+                "
+                    one
+                    └─┬ two
+                      ├── five
+                      └── six
+                "
+                .unindent()
+            );
+            assert_eq!(tree.parent_of(6).map(|node| node.id), Some(2));
+        }
+    }
+
     mod k_iterators {
         use super::*;
         use pretty_assertions::assert_eq;
@@ -613,5 +1078,314 @@ mod test {
                 vec![1, 2, 3, 4]
             );
         }
+
+        #[test]
+        fn b_turns_the_forest_into_rows() {
+            let tree = Forest::new_forest(
+                vec![
+                    TestNode::new(1, None),
+                    TestNode::new(2, Some(1)),
+                    TestNode::new(3, Some(2)),
+                    TestNode::new(4, Some(1)),
+                ]
+                .into_iter(),
+            );
+            let expected: Vec<(String, Option<usize>, usize)> = tree
+                .render_forest_prefixes()
+                .into_iter()
+                .map(|(prefix, node)| (prefix, tree.parent_of(node.id).map(Node::id), node.id))
+                .collect();
+            let rows = tree.into_rows();
+            let rows: Vec<(String, Option<usize>, usize)> = rows
+                .iter()
+                .map(|row| (row.prefix(&rows), row.parent, row.node.id))
+                .collect();
+            assert_eq!(rows, expected);
+        }
+    }
+
+    mod l_robustness {
+        use super::*;
+        use pretty_assertions::assert_eq;
+        use proptest::prelude::*;
+
+        #[test]
+        fn a_nodes_with_missing_parents_become_roots() {
+            let (tree, anomalies) = Forest::build(
+                vec![
+                    TestNode::new(1, None),
+                    TestNode::new(2, Some(7)),
+                    TestNode::new(3, Some(2)),
+                ]
+                .into_iter(),
+            );
+            assert_eq!(
+                tree.test_format(),
+                "
+                    one
+                    two
+                    └── three
+                "
+                .unindent()
+            );
+            assert_eq!(anomalies, vec![Anomaly::MissingParent { id: 2, parent: 7 }]);
+        }
+
+        #[test]
+        fn b_cycles_get_broken() {
+            let (tree, anomalies) = Forest::build(
+                vec![
+                    TestNode::new(1, None),
+                    TestNode::new(2, Some(4)),
+                    TestNode::new(3, Some(2)),
+                    TestNode::new(4, Some(3)),
+                    TestNode::new(5, Some(5)),
+                ]
+                .into_iter(),
+            );
+            assert_eq!(
+                tree.test_format(),
+                "
+                    one
+                    two
+                    └─┬ three
+                      └── four
+                    five
+                "
+                .unindent()
+            );
+            assert_eq!(
+                anomalies,
+                vec![Anomaly::Cycle { id: 2 }, Anomaly::Cycle { id: 5 }]
+            );
+        }
+
+        #[test]
+        fn c_later_duplicates_get_dropped() {
+            let (tree, anomalies) = Forest::build(
+                vec![
+                    TestNode::new(1, None),
+                    TestNode::new(2, Some(1)),
+                    TestNode::new(1, Some(2)),
+                ]
+                .into_iter(),
+            );
+            assert_eq!(
+                tree.test_format(),
+                "
+                    one
+                    └── two
+                "
+                .unindent()
+            );
+            assert_eq!(anomalies, vec![Anomaly::DuplicateId { id: 1 }]);
+        }
+
+        fn nodes() -> impl Strategy<Value = Vec<TestNode>> {
+            prop::collection::vec((0..20usize, prop::option::of(0..25usize)), 0..40).prop_map(
+                |nodes| {
+                    nodes
+                        .into_iter()
+                        .map(|(id, parent)| TestNode::new(id, parent))
+                        .collect()
+                },
+            )
+        }
+
+        fn check_parents(forest: &Forest<TestNode>, parent: Option<usize>, roots: &mut Vec<usize>) {
+            for tree in forest.trees() {
+                match parent {
+                    Some(parent) => assert_eq!(tree.node().parent, Some(parent)),
+                    None => roots.push(tree.node().id),
+                }
+                check_parents(tree.children(), Some(tree.node().id), roots);
+            }
+        }
+
+        proptest! {
+            #[test]
+            fn d_every_id_shows_up_exactly_once(nodes in nodes()) {
+                let mut expected: Vec<usize> = nodes.iter().map(|node| node.id).collect();
+                expected.sort();
+                expected.dedup();
+                let (tree, _) = Forest::build(nodes.into_iter());
+                let mut ids: Vec<usize> = tree.iter().map(Node::id).collect();
+                ids.sort();
+                prop_assert_eq!(ids, expected);
+            }
+
+            #[test]
+            fn e_children_are_shown_under_their_parent(nodes in nodes()) {
+                let (tree, _) = Forest::build(nodes.into_iter());
+                check_parents(&tree, None, &mut Vec::new());
+            }
+
+            #[test]
+            fn f_roots_are_explained(nodes in nodes()) {
+                let parents: HashMap<usize, Option<usize>> = nodes
+                    .iter()
+                    .rev()
+                    .map(|node| (node.id, node.parent))
+                    .collect();
+                let (tree, anomalies) = Forest::build(nodes.into_iter());
+                let mut roots = Vec::new();
+                check_parents(&tree, None, &mut roots);
+                for root in roots {
+                    let explained = match parents[&root] {
+                        None => true,
+                        Some(parent) => anomalies.contains(&Anomaly::MissingParent { id: root, parent })
+                            || anomalies.contains(&Anomaly::Cycle { id: root }),
+                    };
+                    prop_assert!(explained, "unexplained root: {}", root);
+                }
+            }
+        }
+    }
+
+    mod m_updating {
+        use super::*;
+        use pretty_assertions::assert_eq;
+        use proptest::prelude::*;
+
+        #[derive(Debug, Clone, PartialEq)]
+        struct TestNode {
+            id: usize,
+            parent: Option<usize>,
+            value: i32,
+        }
+
+        impl Node for TestNode {
+            type Id = usize;
+
+            fn id(&self) -> usize {
+                self.id
+            }
+
+            fn parent(&self) -> Option<usize> {
+                self.parent
+            }
+
+            fn accumulate_from(&mut self, other: &Self) {
+                self.value += other.value;
+            }
+        }
+
+        fn node(id: usize, parent: Option<usize>, value: i32) -> TestNode {
+            TestNode { id, parent, value }
+        }
+
+        /// (id, parent in the forest, accumulated value), sorted by id
+        fn structure(forest: Forest<TestNode>) -> Vec<(usize, Option<usize>, i32)> {
+            let mut result: Vec<_> = forest
+                .into_rows()
+                .into_iter()
+                .map(|row| (row.node.id, row.parent, row.node.value))
+                .collect();
+            result.sort();
+            result
+        }
+
+        #[test]
+        fn a_replaces_nodes_and_accumulates_again() {
+            let mut forest =
+                Forest::new_forest(vec![node(1, None, 1), node(2, Some(1), 2)].into_iter());
+            forest.update(vec![node(1, None, 10), node(2, Some(1), 20)].into_iter());
+            assert_eq!(structure(forest), vec![(1, None, 30), (2, Some(1), 20)]);
+        }
+
+        #[test]
+        fn b_adds_removes_and_moves_nodes() {
+            let mut forest = Forest::new_forest(
+                vec![
+                    node(1, None, 1),
+                    node(2, Some(1), 1),
+                    node(3, Some(2), 1),
+                    node(4, Some(1), 1),
+                ]
+                .into_iter(),
+            );
+            let anomalies = forest.update(
+                vec![
+                    node(1, None, 1),
+                    node(3, Some(4), 1),
+                    node(4, Some(1), 1),
+                    node(5, Some(3), 1),
+                ]
+                .into_iter(),
+            );
+            assert_eq!(anomalies, vec![]);
+            assert_eq!(
+                structure(forest),
+                vec![
+                    (1, None, 4),
+                    (3, Some(4), 2),
+                    (4, Some(1), 3),
+                    (5, Some(3), 1)
+                ]
+            );
+        }
+
+        #[test]
+        fn c_children_of_removed_nodes_become_roots() {
+            let mut forest = Forest::new_forest(
+                vec![node(1, None, 1), node(2, Some(1), 1), node(3, Some(2), 1)].into_iter(),
+            );
+            let anomalies = forest.update(vec![node(1, None, 1), node(3, Some(2), 1)].into_iter());
+            assert_eq!(anomalies, vec![Anomaly::MissingParent { id: 3, parent: 2 }]);
+            assert_eq!(structure(forest), vec![(1, None, 1), (3, None, 1)]);
+        }
+
+        #[test]
+        fn d_filtered_keeps_ancestors_and_descendants() {
+            let forest = Forest::new_forest(
+                vec![
+                    node(1, None, 0),
+                    node(2, Some(1), 0),
+                    node(3, Some(2), 0),
+                    node(4, Some(1), 0),
+                ]
+                .into_iter(),
+            );
+            let filtered = forest.filtered(|node| node.id == 2);
+            assert_eq!(
+                structure(filtered),
+                vec![(1, None, 0), (2, Some(1), 0), (3, Some(2), 0)]
+            );
+        }
+
+        fn nodes() -> impl Strategy<Value = Vec<TestNode>> {
+            prop::collection::vec((0..20usize, prop::option::of(0..25usize), 0..10i32), 0..40)
+                .prop_map(|nodes| {
+                    nodes
+                        .into_iter()
+                        .map(|(id, parent, value)| node(id, parent, value))
+                        .collect()
+                })
+        }
+
+        proptest! {
+            #[test]
+            fn e_updating_is_the_same_as_building(old in nodes(), new in nodes()) {
+                let mut forest = Forest::new_forest(old.into_iter());
+                let anomalies = forest.update(new.clone().into_iter());
+                let (expected, expected_anomalies) = Forest::build(new.into_iter());
+                prop_assert_eq!(structure(forest), structure(expected));
+                let mut anomalies: Vec<String> = anomalies.iter().map(|a| format!("{a:?}")).collect();
+                let mut expected_anomalies: Vec<String> =
+                    expected_anomalies.iter().map(|a| format!("{a:?}")).collect();
+                anomalies.sort();
+                expected_anomalies.sort();
+                prop_assert_eq!(anomalies, expected_anomalies);
+            }
+
+            #[test]
+            fn f_filtered_is_the_same_as_filter(nodes in nodes(), modulus in 1..5usize) {
+                let forest = Forest::new_forest(nodes.clone().into_iter());
+                let filtered = forest.filtered(|node| node.id % modulus == 0);
+                let mut expected = Forest::new_forest(nodes.into_iter());
+                expected.filter(|node| node.id % modulus == 0);
+                prop_assert_eq!(structure(filtered), structure(expected));
+            }
+        }
     }
 }
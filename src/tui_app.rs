@@ -1,6 +1,6 @@
 use crate::R;
 use crossterm::{
-    event::{self, KeyEvent, KeyEventKind},
+    event::{self, DisableMouseCapture, EnableMouseCapture, KeyEvent, KeyEventKind, MouseEvent},
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
     ExecutableCommand,
 };
@@ -22,10 +22,12 @@ use std::{
 use std::{io::Stdout, time::Duration};
 
 pub(crate) trait TuiApp {
-    fn tick(&mut self);
+    fn tick(&mut self) -> R<UpdateResult>;
 
     fn update(&mut self, event: KeyEvent) -> R<UpdateResult>;
 
+    fn update_mouse(&mut self, event: MouseEvent) -> R<UpdateResult>;
+
     fn render(&mut self, area: Rect, buf: &mut Buffer);
 }
 
@@ -44,15 +46,16 @@ impl<T: TuiApp> StatefulWidget for &mut AppWrapper<T> {
     }
 }
 
-pub(crate) fn run_ui<T: TuiApp>(app: T) -> R<()> {
+pub(crate) fn run_ui<T: TuiApp>(app: &mut T, tick_length: Duration) -> R<()> {
     let termination_signal_received = setup_signal_handlers()?;
     stdout().execute(EnterAlternateScreen)?;
+    stdout().execute(EnableMouseCapture)?;
     enable_raw_mode()?;
     std::panic::set_hook(Box::new(|panic_info| {
         let _ = reset_terminal();
         eprintln!("panic: {}", panic_info);
     }));
-    match main_loop(app, termination_signal_received) {
+    match main_loop(app, tick_length, termination_signal_received) {
         Err(err) => {
             let _ = reset_terminal();
             Err(err)
@@ -65,18 +68,24 @@ pub(crate) fn run_ui<T: TuiApp>(app: T) -> R<()> {
 }
 
 fn reset_terminal() -> R<()> {
+    stdout().execute(DisableMouseCapture)?;
     stdout().execute(LeaveAlternateScreen)?;
     disable_raw_mode()?;
     Ok(())
 }
 
-fn main_loop<T: TuiApp>(mut app: T, termination_signal_received: Arc<AtomicBool>) -> R<()> {
+fn main_loop<T: TuiApp>(
+    app: &mut T,
+    tick_length: Duration,
+    termination_signal_received: Arc<AtomicBool>,
+) -> R<()> {
     let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
     terminal.clear()?;
-    let tick_length = Duration::from_millis(1000);
     let mut last_tick = Instant::now();
-    app.tick();
-    redraw(&mut terminal, &mut app)?;
+    if let UpdateResult::Exit = app.tick()? {
+        return Ok(());
+    }
+    redraw(&mut terminal, app)?;
     loop {
         if termination_signal_received.load(Ordering::Relaxed) {
             break;
@@ -87,20 +96,27 @@ fn main_loop<T: TuiApp>(mut app: T, termination_signal_received: Arc<AtomicBool>
                 .unwrap_or_default(),
         )?;
         if has_event {
-            let event = event::read()?;
-            if let event::Event::Key(key) = event {
-                if key.kind == KeyEventKind::Press {
-                    match app.update(key)? {
-                        UpdateResult::Continue => {}
-                        UpdateResult::Exit => break,
-                    }
+            let update_result = match event::read()? {
+                event::Event::Key(key) if key.kind == KeyEventKind::Press => app.update(key)?,
+                event::Event::Mouse(mouse) => app.update_mouse(mouse)?,
+                event::Event::Resize(_, _) => {
+                    terminal.autoresize()?;
+                    UpdateResult::Continue
                 }
+                _ => UpdateResult::Continue,
+            };
+            match update_result {
+                UpdateResult::Continue => {}
+                UpdateResult::Exit => break,
             }
         } else {
-            app.tick();
+            let tick_result = app.tick()?;
             last_tick = Instant::now();
+            if let UpdateResult::Exit = tick_result {
+                break;
+            }
         }
-        redraw(&mut terminal, &mut app)?;
+        redraw(&mut terminal, app)?;
     }
     Ok(())
 }